@@ -0,0 +1,88 @@
+//! Compares the memory use and speed of the ledgers when storing deposits.
+//!
+//! Run with `cargo bench --bench ledger`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use csv_reader::{
+    client::{Client, RawClientId},
+    ledger::{CompactLedger, DenseLedger, Ledger, SpillLedger},
+    transaction::{deposit::Deposit, RawTransactionId, TransactionId, Transfer},
+    transaction_record::TransactionRecord,
+};
+use rust_decimal::Decimal;
+
+const RECORDS: RawTransactionId = 10_000_000;
+
+/// Keeps track of the number of bytes allocated through the system allocator.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(allocated, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn bench<L: Ledger>(name: &str, new: impl FnOnce() -> L) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+
+    let start = Instant::now();
+    let mut ledger = new();
+    for i in 0..RECORDS {
+        let deposit = Deposit::new(
+            Client::new((i % 1000) as RawClientId),
+            TransactionId::new(i),
+            Decimal::new((i % 100_000) as i64, 4),
+        );
+        ledger.insert(TransactionRecord::new(Transfer::Deposit(deposit)));
+    }
+    let inserted = start.elapsed();
+
+    let start = Instant::now();
+    let found = (0..RECORDS)
+        .step_by(7)
+        .filter(|&i| ledger.get(TransactionId::new(i)).is_some())
+        .count();
+    let looked_up = start.elapsed();
+
+    let retained = ALLOCATED.load(Ordering::Relaxed) - before;
+    let peak = PEAK.load(Ordering::Relaxed) - before;
+    println!(
+        "{name:<8} {:>6} MiB retained ({:>3} bytes/record), {:>6} MiB peak, insert {:>8.1?}, {found} lookups {:>8.1?}",
+        retained / (1024 * 1024),
+        retained / RECORDS as usize,
+        peak / (1024 * 1024),
+        inserted,
+        looked_up,
+    );
+}
+
+fn main() {
+    println!("{RECORDS} deposits with sequential ids");
+    bench("hash", HashMap::<TransactionId, TransactionRecord>::new);
+    bench("compact", CompactLedger::new);
+    bench("dense", DenseLedger::new);
+    bench("spill", || {
+        let path = std::env::temp_dir().join("csv-reader-bench.ledger");
+        SpillLedger::create(path, 64 * 1024 * 1024).expect("Could not create spill file")
+    });
+}
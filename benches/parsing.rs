@@ -0,0 +1,50 @@
+//! Compares the sequential `transactions` reader with the parallel parsing pipeline.
+//!
+//! Run with `cargo bench --bench parsing`.
+
+use std::{io::Cursor, time::Instant};
+
+use csv_reader::transaction::{pipeline::parallel_transactions, transactions};
+
+const ROWS: u32 = 2_000_000;
+
+fn input() -> Vec<u8> {
+    let mut data = String::from("type, client, tx, amount\n");
+    for i in 0..ROWS {
+        let client = i % 1000;
+        let row = match i % 10 {
+            0..=5 => format!("deposit, {client}, {i}, {}.{:04}\n", i % 5000, i % 10000),
+            6..=8 => format!("withdrawal, {client}, {i}, {}.5\n", i % 100),
+            _ => format!("dispute, {client}, {}\n", i - 9),
+        };
+        data.push_str(&row);
+    }
+    data.into_bytes()
+}
+
+fn bench<I, T, E>(name: &str, parsed: I)
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    let start = Instant::now();
+    let ok = parsed.filter(|tx| tx.is_ok()).count();
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<24} {ok} rows in {:>8.1?} ({:.0} rows/s)",
+        elapsed,
+        ok as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    let data = input();
+    println!("{} MiB of input", data.len() / (1024 * 1024));
+
+    bench("transactions", transactions(Cursor::new(&data)));
+    for workers in [1, 2, 4, 8] {
+        bench(
+            &format!("parallel ({workers} workers)"),
+            parallel_transactions(Cursor::new(data.clone()), workers),
+        );
+    }
+}
@@ -4,32 +4,35 @@ use serde::{ser::SerializeStruct, Serialize, Serializer};
 use crate::{
     client::Client,
     error::TransactionError,
-    transaction::{Mutation, Transfer},
+    transaction::{deposit::Deposit, Mutation, Transfer},
 };
 
 /// Represents a Users account
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Account {
     client: Client,
     available: Decimal,
     held: Decimal,
+    // Funds of deposits held for review, not part of the total until they are released.
+    pending: Decimal,
     locked: bool,
 }
 
+/// Rounds the amount to four decimals as written in the output, applying bankers rounding.
+pub fn round(x: Decimal) -> Decimal {
+    x.round_dp_with_strategy(4, rust_decimal::RoundingStrategy::MidpointNearestEven)
+}
+
 impl Serialize for Account {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        // Apply bankers rounding
-        let round = |x: Decimal| {
-            x.round_dp_with_strategy(4, rust_decimal::RoundingStrategy::MidpointNearestEven)
-        };
-
-        let mut s = serializer.serialize_struct("Account", 5)?;
+        let mut s = serializer.serialize_struct("Account", 6)?;
         s.serialize_field("client", &self.client)?;
         s.serialize_field("available", &round(self.available))?;
         s.serialize_field("held", &round(self.held))?;
+        s.serialize_field("pending", &round(self.pending))?;
         s.serialize_field("total", &round(self.total()))?;
         s.serialize_field("locked", &self.locked)?;
         s.end()
@@ -42,10 +45,44 @@ impl Account {
             client,
             available: Decimal::new(0, 0),
             held: Decimal::new(0, 0),
+            pending: Decimal::new(0, 0),
             locked: false,
         }
     }
 
+    /// Restores an account from its parts, used when resuming from persisted accounts.
+    pub fn from_parts(
+        client: Client,
+        available: Decimal,
+        held: Decimal,
+        pending: Decimal,
+        locked: bool,
+    ) -> Self {
+        Self {
+            client,
+            available,
+            held,
+            pending,
+            locked,
+        }
+    }
+
+    pub fn client(&self) -> Client {
+        self.client
+    }
+
+    pub fn available(&self) -> Decimal {
+        self.available
+    }
+
+    pub fn held(&self) -> Decimal {
+        self.held
+    }
+
+    pub fn pending(&self) -> Decimal {
+        self.pending
+    }
+
     /// Returns the computed property `total`
     pub fn total(&self) -> Decimal {
         self.available + self.held
@@ -80,13 +117,19 @@ impl Account {
         Ok(())
     }
 
+    /// Puts the funds of a deposit that is held for review in the pending bucket.
+    pub fn handle_pending(&mut self, deposit: &Deposit) {
+        self.pending += deposit.amount();
+    }
+
     /// Handles the mutation on the account
     pub fn handle_mutation(
         &mut self,
         mutation: &Mutation,
         amount: Decimal,
     ) -> Result<(), TransactionError> {
-        if self.locked {
+        // Pending funds are settled like deposits, which a locked account still accepts
+        if self.locked && !matches!(mutation, Mutation::Release(_) | Mutation::Reject(_)) {
             return Err(TransactionError::AccountLocked);
         }
 
@@ -103,6 +146,15 @@ impl Account {
                 self.lock();
                 self.held -= amount;
             }
+            // Finalizing settles the transaction without moving funds
+            Mutation::Finalize(_) => {}
+            Mutation::Release(_) => {
+                self.pending -= amount;
+                self.available += amount;
+            }
+            Mutation::Reject(_) => {
+                self.pending -= amount;
+            }
         }
         Ok(())
     }
@@ -125,7 +177,7 @@ mod tests {
     fn test_account() {
         let mut account = Account::new(Client::new(1));
         assert_eq!(account.total(), Decimal::new(0, 0));
-        assert_eq!(account.locked(), false);
+        assert!(!account.locked());
 
         account
             .handle_transfer(&Transfer::Deposit(Deposit::new(
@@ -154,7 +206,7 @@ mod tests {
         assert_eq!(account.available, Decimal::new(-50, 0));
         assert_eq!(account.held, Decimal::new(100, 0));
         assert_eq!(account.total(), Decimal::new(50, 0));
-        assert_eq!(account.locked(), false);
+        assert!(!account.locked());
 
         account
             .handle_mutation(
@@ -163,7 +215,7 @@ mod tests {
             )
             .unwrap();
         assert_eq!(account.total(), Decimal::new(50, 0));
-        assert_eq!(account.locked(), false);
+        assert!(!account.locked());
 
         account
             .handle_mutation(
@@ -172,6 +224,6 @@ mod tests {
             )
             .unwrap();
         assert_eq!(account.total(), Decimal::new(-50, 0));
-        assert_eq!(account.locked(), true);
+        assert!(account.locked());
     }
 }
@@ -0,0 +1,169 @@
+use std::{collections::HashMap, fmt, hash::BuildHasher};
+
+use crate::{account::Account, client::Client};
+
+/// Represents the storage of the accounts of a trial balance.
+pub trait AccountStore: fmt::Debug + Send {
+    fn get(&self, client: Client) -> Option<&Account>;
+
+    fn get_mut(&mut self, client: Client) -> Option<&mut Account>;
+
+    /// Stores the account, replacing the account of the same client.
+    fn insert(&mut self, account: Account);
+
+    /// Returns every account in no particular order.
+    fn iter(&self) -> Box<dyn Iterator<Item = &Account> + '_>;
+
+    /// Returns the account of the client, opening a new one when the client has none yet.
+    fn get_or_insert(&mut self, client: Client) -> &mut Account {
+        if self.get(client).is_none() {
+            self.insert(Account::new(client));
+        }
+        self.get_mut(client).expect("Account was just inserted")
+    }
+
+    /// Returns a copy of all accounts ordered by client.
+    fn snapshot(&self) -> Vec<Account> {
+        let mut accounts: Vec<Account> = self.iter().cloned().collect();
+        accounts.sort_by_key(|account| account.client());
+        accounts
+    }
+}
+
+impl<S> AccountStore for HashMap<Client, Account, S>
+where
+    S: BuildHasher + Send,
+{
+    fn get(&self, client: Client) -> Option<&Account> {
+        HashMap::get(self, &client)
+    }
+
+    fn get_mut(&mut self, client: Client) -> Option<&mut Account> {
+        HashMap::get_mut(self, &client)
+    }
+
+    fn insert(&mut self, account: Account) {
+        HashMap::insert(self, account.client(), account);
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Account> + '_> {
+        Box::new(self.values())
+    }
+
+    fn get_or_insert(&mut self, client: Client) -> &mut Account {
+        self.entry(client).or_insert_with(|| Account::new(client))
+    }
+}
+
+/// Represents an account store that keeps the accounts in a vector indexed by client id.
+///
+/// This avoids hashing on every transaction. The vector grows up to the highest client id seen,
+/// which is bounded by the `u16` id at a few megabytes. With the `wide-ids` feature there is no such bound,
+/// so the command line does not offer it then.
+#[derive(Debug, Default)]
+pub struct DenseAccountStore {
+    accounts: Vec<Option<Account>>,
+}
+
+impl DenseAccountStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store with room for the clients with an id below `capacity`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            accounts: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+impl AccountStore for DenseAccountStore {
+    fn get(&self, client: Client) -> Option<&Account> {
+        self.accounts
+            .get(client.id() as usize)
+            .and_then(Option::as_ref)
+    }
+
+    fn get_mut(&mut self, client: Client) -> Option<&mut Account> {
+        self.accounts
+            .get_mut(client.id() as usize)
+            .and_then(Option::as_mut)
+    }
+
+    fn insert(&mut self, account: Account) {
+        let index = account.client().id() as usize;
+        if index >= self.accounts.len() {
+            self.accounts.resize(index + 1, None);
+        }
+        self.accounts[index] = Some(account);
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Account> + '_> {
+        Box::new(self.accounts.iter().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rust_decimal::Decimal;
+
+    use super::{AccountStore, DenseAccountStore};
+    use crate::{
+        account::Account, client::Client, transaction::transactions, trial_balance::TrialBalance,
+    };
+
+    fn test_account_store<S: AccountStore>(mut store: S) {
+        assert_eq!(store.get(Client::new(3)), None);
+        store.get_or_insert(Client::new(3)).lock();
+        store.insert(Account::from_parts(
+            Client::new(65_535),
+            Decimal::new(15, 1),
+            Decimal::ZERO,
+            Decimal::ZERO,
+            false,
+        ));
+        store.get_or_insert(Client::new(0));
+
+        assert!(store.get(Client::new(3)).is_some_and(Account::locked));
+        assert!(!store.get(Client::new(0)).unwrap().locked());
+        assert_eq!(store.get(Client::new(1)), None);
+        assert_eq!(store.get_mut(Client::new(2)), None);
+        assert_eq!(store.iter().count(), 3);
+        let clients: Vec<_> = store
+            .snapshot()
+            .iter()
+            .map(|account| account.client().id())
+            .collect();
+        assert_eq!(clients, [0, 3, 65_535]);
+    }
+
+    #[test]
+    fn test_account_stores() {
+        test_account_store(HashMap::new());
+        test_account_store(DenseAccountStore::new());
+    }
+
+    #[test]
+    fn test_dense_account_store_matches_hash() {
+        let data = "type, client, tx, amount
+            deposit, 7, 1, 10.5
+            deposit, 2, 2, 20
+            dispute, 7, 1,
+            withdrawal, 2, 3, 25
+            chargeback, 7, 1,
+            deposit, 7, 4, 1";
+        let mut dense = TrialBalance::new().with_account_store(DenseAccountStore::new());
+        let mut hash = TrialBalance::new();
+        for tx in transactions(data.as_bytes()).map(Result::unwrap) {
+            assert_eq!(
+                dense.handle_transaction(tx.clone()),
+                hash.handle_transaction(tx)
+            );
+        }
+        assert_eq!(dense.snapshot(), hash.snapshot());
+        assert_eq!(dense.verify(), Ok(()));
+    }
+}
@@ -0,0 +1,799 @@
+use rust_decimal::Decimal;
+
+use csv_reader::{
+    client::Client,
+    policy::{DisputePolicy, RetentionPolicy},
+    snapshot::Cutoff,
+    transaction::{interner::TxIds, Timestamp},
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub const USAGE: &str = "Usage: csv-reader [command] <transactions_file> [options]
+
+Commands:
+    (none)                         Write the accounts after processing all transactions
+    balances-at                    Write the accounts as they stood at each cut-off
+    statement                      Write every transaction of a client with running balances
+    journal                        Write the debit/credit trial balance of the journal
+    serve                          Handle transactions submitted over HTTP, starting from the optional file
+    validate                       Check every row of the file without applying any transaction
+    reconcile                      Compare a balances file against a second balances file or a transactions file
+
+Options:
+    --dispute-window-days <days>   Reject disputes filed more than <days> after the transfer
+    --auto-resolve-days <days>     Resolve disputes that are still open after <days>
+    --auto-resolved <file>         Write the generated resolves to a csv file (requires --auto-resolve-days)
+    --retain-days <days>           Evict transactions from the ledger once they are older than <days>
+    --retain-records <n>           Evict the oldest transactions once the ledger holds more than <n>
+    --at-seq <rows>                balances-at: capture the accounts after <rows> rows
+    --at-time <secs>               balances-at: capture the accounts at Unix time <secs>
+    --client <id>                  statement: the client to write the statement for
+    --verify                       Verify the invariants of the accounts and ledger at the end (not with serve)
+    --verify-every <n>             Verify the invariants after every <n> transactions
+    --threads <n>                  Process the transactions on <n> threads sharded by client
+    --parse-threads <n>            Parse the input on <n> threads
+    --strict                       Reject ragged rows, blank lines, invalid UTF-8 and amounts on mutations,
+                                   reporting each rejected row with its line and column
+    --dialect <file>               Read the input in a dialect from a TOML file of named profiles
+    --profile <name>               The profile of --dialect to use, optional if the file has one profile
+    --tx-ids <kind>                Read transaction ids as numeric (default), string or uuid ids
+    --ledger <kind>                Store transactions in a hash (default), compact, dense or spill ledger
+    --ledger-memory <MiB>          spill: memory to keep transactions in before spilling (default 256)
+    --spill-dir <dir>              spill: directory of the spill file (default the temp directory)
+    --accounts <kind>              Store accounts in a hash (default) or dense store
+    --rules <file>                 Screen transactions with the rules in a TOML file before applying them
+    --screening-report <file>      Write every transaction a rule fired on to a csv file (requires --rules)
+    --stats                        Write the counts, totals and throughput of the run to stderr
+    --stats-json <file>            Write the counts, totals and throughput of the run to a JSON file
+    --db <file>                    Persist the state in a SQLite file and resume from it (sqlite feature)
+    --transactions <file>          reconcile: compare against the accounts after processing <file>
+    --tolerance <amount>           reconcile: largest difference between amounts that still matches (default 0)
+    --addr <host:port>             serve: the address to listen on (default 127.0.0.1:8080)
+    --metrics-file <file>          serve: write the Prometheus metrics to <file> for the textfile collector
+    --metrics-interval <secs>      serve: seconds between writes of --metrics-file (default 15)";
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+/// Represents what the program should do with the transactions.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Command {
+    /// Write the accounts after processing all transactions.
+    #[default]
+    Process,
+    /// Write the accounts as they stood at each of the cut-offs.
+    BalancesAt(Vec<Cutoff>),
+    /// Write the statement of a single client.
+    Statement(Option<Client>),
+    /// Write the trial balance of the double-entry journal.
+    Journal,
+    /// Handle transactions submitted over HTTP on the address.
+    Serve(String),
+    /// Check the rows of the file without applying them.
+    Validate,
+    /// Compare the balances file against the counterpart.
+    Reconcile(Option<Counterpart>),
+}
+
+/// Represents what a balances file is reconciled against.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Counterpart {
+    /// Another balances file.
+    Balances(String),
+    /// The accounts after processing a transactions file.
+    Transactions(String),
+}
+
+/// Represents how the ledger stores the transaction records.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerKind {
+    /// A hash map of full transaction records.
+    #[default]
+    Hash,
+    /// A hash map of packed records with a fast integer hasher.
+    Compact,
+    /// A vector of packed records indexed by transaction id.
+    Dense,
+    /// Packed records in memory up to a cap, the rest in a file.
+    Spill,
+}
+
+impl std::str::FromStr for LedgerKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hash" => Ok(LedgerKind::Hash),
+            "compact" => Ok(LedgerKind::Compact),
+            "dense" => Ok(LedgerKind::Dense),
+            "spill" => Ok(LedgerKind::Spill),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Represents how the accounts are stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AccountStoreKind {
+    /// A hash map keyed by client.
+    #[default]
+    Hash,
+    /// A vector indexed by client id.
+    Dense,
+}
+
+impl std::str::FromStr for AccountStoreKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hash" => Ok(AccountStoreKind::Hash),
+            "dense" => Ok(AccountStoreKind::Dense),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Represents the options the program was started with.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub command: Command,
+    pub input: String,
+    pub dispute_policy: DisputePolicy,
+    /// When transactions are evicted from the ledger.
+    pub retention: RetentionPolicy,
+    /// Verify the invariants after processing all transactions.
+    pub verify: bool,
+    /// Verify the invariants periodically while processing.
+    pub verify_every: Option<u64>,
+    /// Number of threads the accounts are sharded over when processing.
+    pub threads: usize,
+    /// Number of threads the input is parsed on.
+    pub parse_threads: usize,
+    /// Read the input strictly, reporting every rejected row.
+    pub strict: bool,
+    /// TOML file with the named dialects the input can be written in.
+    pub dialect: Option<String>,
+    /// Name of the dialect the input is written in.
+    pub profile: Option<String>,
+    /// How the transaction ids of the input are written.
+    pub tx_ids: TxIds,
+    /// How the transaction records are stored.
+    pub ledger: LedgerKind,
+    /// Memory in MiB the spill ledger keeps records in.
+    pub ledger_memory: Option<usize>,
+    /// Directory the spill ledger writes its file to.
+    pub spill_dir: Option<String>,
+    /// How the accounts are stored.
+    pub accounts: AccountStoreKind,
+    /// SQLite database the state is persisted in.
+    pub db: Option<String>,
+    /// TOML file with the rules transactions are screened with.
+    pub rules: Option<String>,
+    /// File the transactions a rule fired on are written to.
+    pub screening_report: Option<String>,
+    /// File the resolves generated for expired disputes are written to.
+    pub auto_resolved: Option<String>,
+    /// Write the statistics of the run to stderr.
+    pub stats: bool,
+    /// File the statistics of the run are written to as JSON.
+    pub stats_json: Option<String>,
+    /// Largest difference between reconciled amounts that still matches.
+    pub tolerance: Decimal,
+    /// File the metrics of the service are written to periodically.
+    pub metrics_file: Option<String>,
+    /// Seconds between writes of the metrics file.
+    pub metrics_interval: Option<u64>,
+}
+
+impl Options {
+    /// Parses the command line arguments, excluding the program name.
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter().peekable();
+        let command = match args.peek().map(String::as_str) {
+            Some("balances-at") => Command::BalancesAt(Vec::new()),
+            Some("statement") => Command::Statement(None),
+            Some("journal") => Command::Journal,
+            Some("serve") => Command::Serve(DEFAULT_ADDR.to_string()),
+            Some("validate") => Command::Validate,
+            Some("reconcile") => Command::Reconcile(None),
+            _ => Command::Process,
+        };
+        if command != Command::Process {
+            args.next();
+        }
+        let mut options = Options {
+            command,
+            ..Default::default()
+        };
+
+        while let Some(arg) = args.next() {
+            match (arg.as_str(), &mut options.command) {
+                ("--dispute-window-days", _) => {
+                    let secs = parse_days(&arg, args.next())?;
+                    options.dispute_policy = options.dispute_policy.with_window(secs);
+                }
+                ("--auto-resolve-days", _) => {
+                    let secs = parse_days(&arg, args.next())?;
+                    options.dispute_policy = options.dispute_policy.with_auto_resolve_after(secs);
+                }
+                ("--auto-resolved", command)
+                    if !matches!(command, Command::Serve(_) | Command::Validate) =>
+                {
+                    options.auto_resolved = Some(parse_value(&arg, args.next())?);
+                }
+                ("--retain-days", _) => {
+                    let secs = parse_days(&arg, args.next())?;
+                    options.retention = options.retention.with_max_age(secs);
+                }
+                ("--retain-records", _) => {
+                    let n = parse_value(&arg, args.next())?;
+                    options.retention = options.retention.with_max_records(n);
+                }
+                ("--at-seq", Command::BalancesAt(cutoffs)) => {
+                    cutoffs.push(Cutoff::Sequence(parse_value(&arg, args.next())?));
+                }
+                ("--at-time", Command::BalancesAt(cutoffs)) => {
+                    let secs = parse_value(&arg, args.next())?;
+                    cutoffs.push(Cutoff::Timestamp(Timestamp::from_secs(secs)));
+                }
+                ("--client", Command::Statement(client)) => {
+                    *client = Some(Client::new(parse_value(&arg, args.next())?));
+                }
+                ("--verify", command) if !matches!(command, Command::Serve(_)) => {
+                    options.verify = true;
+                }
+                ("--verify-every", _) => {
+                    options.verify_every = Some(parse_value(&arg, args.next())?);
+                }
+                ("--threads", Command::Process) => {
+                    options.threads = parse_value(&arg, args.next())?;
+                }
+                ("--ledger", _) => options.ledger = parse_value(&arg, args.next())?,
+                ("--ledger-memory", _) => {
+                    options.ledger_memory = Some(parse_value(&arg, args.next())?);
+                }
+                ("--spill-dir", _) => options.spill_dir = Some(parse_value(&arg, args.next())?),
+                ("--accounts", _) => options.accounts = parse_value(&arg, args.next())?,
+                ("--db", Command::Process) => options.db = Some(parse_value(&arg, args.next())?),
+                ("--rules", _) => options.rules = Some(parse_value(&arg, args.next())?),
+                ("--screening-report", command) if !matches!(command, Command::Serve(_)) => {
+                    options.screening_report = Some(parse_value(&arg, args.next())?);
+                }
+                ("--stats", command)
+                    if !matches!(command, Command::Serve(_) | Command::Validate) =>
+                {
+                    options.stats = true;
+                }
+                ("--stats-json", command)
+                    if !matches!(command, Command::Serve(_) | Command::Validate) =>
+                {
+                    options.stats_json = Some(parse_value(&arg, args.next())?);
+                }
+                ("--transactions", Command::Reconcile(counterpart @ None)) => {
+                    *counterpart = Some(Counterpart::Transactions(parse_value(&arg, args.next())?));
+                }
+                ("--tolerance", Command::Reconcile(_)) => {
+                    options.tolerance = parse_value(&arg, args.next())?;
+                }
+                ("--addr", Command::Serve(addr)) => {
+                    *addr = args.next().ok_or("Missing value for --addr")?;
+                }
+                ("--metrics-file", Command::Serve(_)) => {
+                    options.metrics_file = Some(parse_value(&arg, args.next())?);
+                }
+                ("--metrics-interval", Command::Serve(_)) => {
+                    options.metrics_interval = Some(parse_value(&arg, args.next())?);
+                }
+                ("--parse-threads", _) => {
+                    options.parse_threads = parse_value(&arg, args.next())?;
+                }
+                ("--strict", command) if *command != Command::Validate => options.strict = true,
+                ("--dialect", command) if *command != Command::Validate => {
+                    options.dialect = Some(parse_value(&arg, args.next())?);
+                }
+                ("--profile", command) if *command != Command::Validate => {
+                    options.profile = Some(parse_value(&arg, args.next())?);
+                }
+                ("--tx-ids", command)
+                    if !matches!(command, Command::Serve(_) | Command::Validate) =>
+                {
+                    options.tx_ids = parse_value(&arg, args.next())?;
+                }
+                (flag, _) if flag.starts_with("--") => {
+                    return Err(format!("Unknown option {flag}"))
+                }
+                _ if options.input.is_empty() => options.input = arg,
+                (_, Command::Reconcile(counterpart @ None)) => {
+                    *counterpart = Some(Counterpart::Balances(arg));
+                }
+                _ => return Err(format!("Unexpected argument {arg}")),
+            }
+        }
+
+        if options.command == Command::BalancesAt(Vec::new()) {
+            return Err("balances-at requires at least one --at-seq or --at-time".to_string());
+        }
+        if options.command == Command::Statement(None) {
+            return Err("statement requires --client".to_string());
+        }
+        if options.ledger != LedgerKind::Spill
+            && (options.ledger_memory.is_some() || options.spill_dir.is_some())
+        {
+            return Err("--ledger-memory and --spill-dir require --ledger spill".to_string());
+        }
+        #[cfg(feature = "wide-ids")]
+        if options.ledger == LedgerKind::Spill || options.accounts == AccountStoreKind::Dense {
+            return Err(
+                "--ledger spill and --accounts dense can not be used with wide ids".to_string(),
+            );
+        }
+        if options.db.is_some() && (options.threads > 1 || options.ledger != LedgerKind::Hash) {
+            return Err("--db can not be combined with --threads or --ledger".to_string());
+        }
+        // The interned ids are not persisted, a resumed run would give them other numbers
+        if options.db.is_some() && options.tx_ids != TxIds::Numeric {
+            return Err("--db requires numeric --tx-ids".to_string());
+        }
+        // Only accounts and records are persisted, what these options keep would be lost on resume
+        if options.db.is_some()
+            && (options.retention.is_enabled()
+                || options.dispute_policy.auto_resolve_after().is_some()
+                || options.rules.is_some())
+        {
+            return Err(
+                "--db can not be combined with --retain-days, --retain-records, --auto-resolve-days or --rules"
+                    .to_string(),
+            );
+        }
+        if options.command == Command::Reconcile(None) {
+            return Err("reconcile requires a second balances file or --transactions".to_string());
+        }
+        if options.tolerance < Decimal::ZERO {
+            return Err("--tolerance can not be negative".to_string());
+        }
+        if options.strict && options.parse_threads > 1 {
+            return Err("--strict can not be combined with --parse-threads".to_string());
+        }
+        if (options.dialect.is_some() || options.tx_ids != TxIds::Numeric)
+            && (options.strict || options.parse_threads > 1)
+        {
+            return Err(
+                "--dialect and --tx-ids can not be combined with --strict or --parse-threads"
+                    .to_string(),
+            );
+        }
+        if options.profile.is_some() && options.dialect.is_none() {
+            return Err("--profile requires --dialect".to_string());
+        }
+        if options.metrics_interval.is_some() && options.metrics_file.is_none() {
+            return Err("--metrics-interval requires --metrics-file".to_string());
+        }
+        if options.metrics_interval == Some(0) {
+            return Err("--metrics-interval must be at least 1 second".to_string());
+        }
+        if options.auto_resolved.is_some() && options.dispute_policy.auto_resolve_after().is_none()
+        {
+            return Err("--auto-resolved requires --auto-resolve-days".to_string());
+        }
+        if options.screening_report.is_some() && options.rules.is_none() {
+            return Err("--screening-report requires --rules".to_string());
+        }
+        // Validation only reads the file, it takes none of the options of processing
+        if options.command == Command::Validate
+            && options
+                != (Options {
+                    command: Command::Validate,
+                    input: options.input.clone(),
+                    ..Default::default()
+                })
+        {
+            return Err("validate does not take processing options".to_string());
+        }
+        if options.input.is_empty() && !matches!(options.command, Command::Serve(_)) {
+            return Err("Missing transactions file".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_value<T>(flag: &str, value: Option<String>) -> Result<T, String>
+where
+    T: std::str::FromStr,
+{
+    let value = value.ok_or_else(|| format!("Missing value for {flag}"))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value {value} for {flag}"))
+}
+
+/// Parses a number of days into seconds.
+fn parse_days(flag: &str, value: Option<String>) -> Result<u64, String> {
+    let days: u64 = parse_value(flag, value)?;
+    days.checked_mul(SECONDS_PER_DAY)
+        .ok_or_else(|| format!("Too many days {days} for {flag}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{
+        AccountStoreKind, Command, Counterpart, LedgerKind, Options, DEFAULT_ADDR, SECONDS_PER_DAY,
+    };
+    use csv_reader::{
+        client::Client,
+        policy::{DisputePolicy, RetentionPolicy},
+        snapshot::Cutoff,
+        transaction::{interner::TxIds, Timestamp},
+    };
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(
+            parse(&["transactions.csv"]),
+            Ok(Options {
+                command: Command::Process,
+                input: "transactions.csv".to_string(),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&[
+                "--dispute-window-days",
+                "120",
+                "transactions.csv",
+                "--auto-resolve-days",
+                "30",
+                "--auto-resolved",
+                "resolves.csv"
+            ]),
+            Ok(Options {
+                command: Command::Process,
+                input: "transactions.csv".to_string(),
+                dispute_policy: DisputePolicy::new()
+                    .with_window(120 * SECONDS_PER_DAY)
+                    .with_auto_resolve_after(30 * SECONDS_PER_DAY),
+                auto_resolved: Some("resolves.csv".to_string()),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--auto-resolved", "resolves.csv"]).is_err());
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a.csv", "b.csv"]).is_err());
+        assert!(parse(&["a.csv", "--dispute-window-days"]).is_err());
+        assert!(parse(&["a.csv", "--dispute-window-days", "999999999999999"]).is_err());
+        assert!(parse(&["a.csv", "--dispute-window-days", "soon"]).is_err());
+        assert_eq!(
+            parse(&["a.csv", "--verify", "--verify-every", "1000"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                verify: true,
+                verify_every: Some(1000),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&["a.csv", "--threads", "4"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                threads: 4,
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["journal", "a.csv", "--threads", "4"]).is_err());
+        assert_eq!(
+            parse(&["a.csv", "--ledger", "dense"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                ledger: LedgerKind::Dense,
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--ledger", "btree"]).is_err());
+        #[cfg(not(feature = "wide-ids"))]
+        assert_eq!(
+            parse(&["a.csv", "--accounts", "dense"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                accounts: AccountStoreKind::Dense,
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--accounts", "vec"]).is_err());
+        #[cfg(not(feature = "wide-ids"))]
+        assert_eq!(
+            parse(&[
+                "a.csv",
+                "--ledger",
+                "spill",
+                "--ledger-memory",
+                "64",
+                "--spill-dir",
+                "/tmp"
+            ]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                ledger: LedgerKind::Spill,
+                ledger_memory: Some(64),
+                spill_dir: Some("/tmp".to_string()),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--ledger-memory", "64"]).is_err());
+        assert_eq!(
+            parse(&["a.csv", "--db", "state.db"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                db: Some("state.db".to_string()),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--db", "state.db", "--threads", "2"]).is_err());
+        assert!(parse(&["journal", "a.csv", "--db", "state.db"]).is_err());
+        for flag in [
+            ["--retain-days", "180"],
+            ["--retain-records", "1000"],
+            ["--auto-resolve-days", "30"],
+            ["--rules", "rules.toml"],
+        ] {
+            assert!(parse(&["a.csv", "--db", "state.db", flag[0], flag[1]]).is_err());
+        }
+        assert_eq!(
+            parse(&[
+                "a.csv",
+                "--rules",
+                "rules.toml",
+                "--screening-report",
+                "screened.csv"
+            ]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                rules: Some("rules.toml".to_string()),
+                screening_report: Some("screened.csv".to_string()),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--screening-report", "screened.csv"]).is_err());
+        assert!(parse(&["serve", "--rules", "r.toml", "--screening-report", "s.csv"]).is_err());
+        assert_eq!(
+            parse(&[
+                "a.csv",
+                "--retain-days",
+                "180",
+                "--retain-records",
+                "1000000"
+            ]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                retention: RetentionPolicy::new()
+                    .with_max_age(180 * SECONDS_PER_DAY)
+                    .with_max_records(1_000_000),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&["journal", "a.csv", "--parse-threads", "4"]),
+            Ok(Options {
+                command: Command::Journal,
+                input: "a.csv".to_string(),
+                parse_threads: 4,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&["a.csv", "--strict"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                strict: true,
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--strict", "--parse-threads", "4"]).is_err());
+        assert_eq!(
+            parse(&[
+                "a.csv",
+                "--dialect",
+                "dialects.toml",
+                "--profile",
+                "partner"
+            ]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                dialect: Some("dialects.toml".to_string()),
+                profile: Some("partner".to_string()),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--profile", "partner"]).is_err());
+        assert!(parse(&["a.csv", "--dialect", "dialects.toml", "--strict"]).is_err());
+        assert_eq!(
+            parse(&["a.csv", "--tx-ids", "uuid"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                tx_ids: TxIds::Uuid,
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--tx-ids", "guid"]).is_err());
+        assert!(parse(&["validate", "a.csv", "--tx-ids", "string"]).is_err());
+        assert!(parse(&["a.csv", "--db", "state.db", "--tx-ids", "string"]).is_err());
+        assert!(parse(&["a.csv", "--db", "state.db", "--tx-ids", "numeric"]).is_ok());
+        assert!(parse(&["validate", "a.csv", "--strict"]).is_err());
+        assert!(parse(&["validate", "a.csv", "--dialect", "dialects.toml"]).is_err());
+        assert!(parse(&["validate", "a.csv", "--profile", "partner"]).is_err());
+        assert_eq!(
+            parse(&["journal", "a.csv", "--stats", "--stats-json", "stats.json"]),
+            Ok(Options {
+                command: Command::Journal,
+                input: "a.csv".to_string(),
+                stats: true,
+                stats_json: Some("stats.json".to_string()),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["serve", "--stats"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_balances_at() {
+        assert_eq!(
+            parse(&[
+                "balances-at",
+                "a.csv",
+                "--at-seq",
+                "10",
+                "--at-time",
+                "1700000000"
+            ]),
+            Ok(Options {
+                command: Command::BalancesAt(vec![
+                    Cutoff::Sequence(10),
+                    Cutoff::Timestamp(Timestamp::from_secs(1700000000)),
+                ]),
+                input: "a.csv".to_string(),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["balances-at", "a.csv"]).is_err());
+        assert!(parse(&["a.csv", "--at-seq", "10"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_statement() {
+        assert_eq!(
+            parse(&["statement", "a.csv", "--client", "7"]),
+            Ok(Options {
+                command: Command::Statement(Some(Client::new(7))),
+                input: "a.csv".to_string(),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["statement", "a.csv"]).is_err());
+        assert!(parse(&["statement", "a.csv", "--client", "18446744073709551616"]).is_err());
+    }
+
+    #[cfg(feature = "wide-ids")]
+    #[test]
+    fn test_parse_wide_ids() {
+        assert!(parse(&["a.csv", "--accounts", "dense"]).is_err());
+        assert!(parse(&["a.csv", "--ledger", "spill"]).is_err());
+        assert_eq!(
+            parse(&["a.csv", "--ledger", "dense", "--accounts", "hash"]),
+            Ok(Options {
+                input: "a.csv".to_string(),
+                ledger: LedgerKind::Dense,
+                accounts: AccountStoreKind::Hash,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_reconcile() {
+        assert_eq!(
+            parse(&["reconcile", "old.csv", "new.csv", "--tolerance", "0.0001"]),
+            Ok(Options {
+                command: Command::Reconcile(Some(Counterpart::Balances("new.csv".to_string()))),
+                input: "old.csv".to_string(),
+                tolerance: Decimal::new(1, 4),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&["reconcile", "old.csv", "--transactions", "tx.csv"]),
+            Ok(Options {
+                command: Command::Reconcile(Some(Counterpart::Transactions("tx.csv".to_string()))),
+                input: "old.csv".to_string(),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["reconcile", "old.csv"]).is_err());
+        assert!(parse(&[
+            "reconcile",
+            "old.csv",
+            "new.csv",
+            "--transactions",
+            "tx.csv"
+        ])
+        .is_err());
+        assert!(parse(&["a.csv", "--tolerance", "1"]).is_err());
+        assert!(parse(&["reconcile", "old.csv", "new.csv", "--tolerance", "-0.01"]).is_err());
+        assert_eq!(
+            parse(&["validate", "a.csv"]),
+            Ok(Options {
+                command: Command::Validate,
+                input: "a.csv".to_string(),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["validate"]).is_err());
+        for flag in [
+            &["--rules", "rules.toml"][..],
+            &["--verify"],
+            &["--verify-every", "100"],
+            &["--ledger", "dense"],
+            &["--retain-days", "30"],
+            &["--dispute-window-days", "30"],
+        ] {
+            let args: Vec<&str> = ["validate", "a.csv"].iter().chain(flag).copied().collect();
+            assert!(parse(&args).is_err(), "{flag:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_serve() {
+        assert_eq!(
+            parse(&["serve"]),
+            Ok(Options {
+                command: Command::Serve(DEFAULT_ADDR.to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse(&["serve", "a.csv", "--addr", "0.0.0.0:9000"]),
+            Ok(Options {
+                command: Command::Serve("0.0.0.0:9000".to_string()),
+                input: "a.csv".to_string(),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["serve", "--addr"]).is_err());
+        // The service never ends, only periodic verification applies
+        assert!(parse(&["serve", "--verify"]).is_err());
+        assert_eq!(
+            parse(&["serve", "--verify-every", "1000"]),
+            Ok(Options {
+                command: Command::Serve(DEFAULT_ADDR.to_string()),
+                verify_every: Some(1000),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["a.csv", "--addr", "0.0.0.0:9000"]).is_err());
+        assert_eq!(
+            parse(&[
+                "serve",
+                "--metrics-file",
+                "csv_reader.prom",
+                "--metrics-interval",
+                "60"
+            ]),
+            Ok(Options {
+                command: Command::Serve(DEFAULT_ADDR.to_string()),
+                metrics_file: Some("csv_reader.prom".to_string()),
+                metrics_interval: Some(60),
+                ..Default::default()
+            })
+        );
+        assert!(parse(&["serve", "--metrics-interval", "60"]).is_err());
+        assert!(parse(&["a.csv", "--metrics-file", "csv_reader.prom"]).is_err());
+    }
+}
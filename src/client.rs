@@ -1,13 +1,22 @@
 use serde::{Deserialize, Serialize};
 
-/// Represents a client by u16 ID.
+/// Integer a client ID is stored as, a `u16` unless the `wide-ids` feature widens it to a `u64`.
+#[cfg(not(feature = "wide-ids"))]
+pub type RawClientId = u16;
+#[cfg(feature = "wide-ids")]
+pub type RawClientId = u64;
+
+/// Represents a client by [`RawClientId`] ID.
 /// IDs are unique
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, Hash)]
-pub struct Client(u16);
+pub struct Client(RawClientId);
 
-#[cfg(test)]
 impl Client {
-    pub fn new(id: u16) -> Self {
+    pub fn new(id: RawClientId) -> Self {
         Self(id)
     }
+
+    pub fn id(&self) -> RawClientId {
+        self.0
+    }
 }
@@ -1,6 +1,6 @@
 use crate::transaction::TransactionId;
 
-#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum TransactionError {
     #[error("Error: Account is locked")]
     AccountLocked,
@@ -12,8 +12,68 @@ pub enum TransactionError {
     ResolveError,
     #[error("Error: Charge back could not be processed on transaction")]
     ChargeBackError,
+    #[error("Error: Finalize could not be processed on transaction")]
+    FinalizeError,
+    #[error("Error: Release could not be processed on transaction")]
+    ReleaseError,
+    #[error("Error: Reject could not be processed on transaction")]
+    RejectError,
     #[error("Error: Duplicate transaction {0:?}")]
     DuplicateTransaction(TransactionId),
     #[error("Error: Missing transaction {0:?}")]
     MissingTransaction(TransactionId),
+    #[error("Error: Dispute window expired for transaction {0:?}")]
+    DisputeWindowExpired(TransactionId),
+    #[error("Error: Transaction {0:?} has been finalized")]
+    TransactionFinalized(TransactionId),
+    #[error("Error: Transaction {0:?} is pending review")]
+    TransactionPending(TransactionId),
+    #[error("Error: Rejected by rule {0}")]
+    RejectedByRule(String),
+    #[error("Error: Held for review by rule {0}")]
+    HeldByRule(String),
+}
+
+impl TransactionError {
+    /// Returns the name of the kind of error as used in the statistics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TransactionError::AccountLocked => "account_locked",
+            TransactionError::InsufficientFunds => "insufficient_funds",
+            TransactionError::DisputeError => "dispute_error",
+            TransactionError::ResolveError => "resolve_error",
+            TransactionError::ChargeBackError => "charge_back_error",
+            TransactionError::FinalizeError => "finalize_error",
+            TransactionError::ReleaseError => "release_error",
+            TransactionError::RejectError => "reject_error",
+            TransactionError::DuplicateTransaction(_) => "duplicate_transaction",
+            TransactionError::MissingTransaction(_) => "missing_transaction",
+            TransactionError::DisputeWindowExpired(_) => "dispute_window_expired",
+            TransactionError::TransactionFinalized(_) => "transaction_finalized",
+            TransactionError::TransactionPending(_) => "transaction_pending",
+            TransactionError::RejectedByRule(_) => "rejected_by_rule",
+            TransactionError::HeldByRule(_) => "held_by_rule",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RulesError {
+    #[error("Could not read rules: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse rules: {0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DialectError {
+    #[error("Could not read dialects: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse dialects: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Profile {profile}: {field} must be a single ASCII character")]
+    NotAscii {
+        profile: String,
+        field: &'static str,
+    },
 }
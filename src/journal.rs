@@ -0,0 +1,312 @@
+use std::{collections::BTreeMap, fmt};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    account::Account,
+    client::Client,
+    transaction::{deposit::Deposit, Mutation, TransactionId, Transfer},
+};
+
+/// Represents an account in the double-entry journal.
+///
+/// Client accounts are liabilities towards the client and grow by credits.
+/// The settlement account is an asset and grows by debits.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum LedgerAccount {
+    /// Funds the client can freely use.
+    ClientAvailable(Client),
+    /// Funds of the client that are held because of a dispute.
+    ClientHeld(Client),
+    /// Funds of deposits of the client that are held for review.
+    ClientPending(Client),
+    /// Funds at the bank or card scheme that settles deposits, withdrawals and charge backs.
+    ExternalSettlement,
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerAccount::ClientAvailable(client) => write!(f, "client:{}:available", client.id()),
+            LedgerAccount::ClientHeld(client) => write!(f, "client:{}:held", client.id()),
+            LedgerAccount::ClientPending(client) => write!(f, "client:{}:pending", client.id()),
+            LedgerAccount::ExternalSettlement => write!(f, "external:settlement"),
+        }
+    }
+}
+
+/// Represents a balanced posting that moves `amount` from the `credit` to the `debit` account.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Posting {
+    tx: TransactionId,
+    debit: LedgerAccount,
+    credit: LedgerAccount,
+    amount: Decimal,
+}
+
+impl Posting {
+    pub fn transaction_id(&self) -> TransactionId {
+        self.tx
+    }
+    pub fn debit(&self) -> LedgerAccount {
+        self.debit
+    }
+    pub fn credit(&self) -> LedgerAccount {
+        self.credit
+    }
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+}
+
+/// Represents a line of the debit/credit trial balance report.
+/// Only one of `debit` and `credit` is non-zero, depending on the side of the net balance.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct TrialBalanceLine {
+    account: String,
+    debit: Decimal,
+    credit: Decimal,
+}
+
+/// Represents a difference between an [`Account`] and the balance derived from the journal.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Discrepancy {
+    pub account: LedgerAccount,
+    pub expected: Decimal,
+    pub actual: Decimal,
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: journal balance {} does not match account balance {}",
+            self.account, self.expected, self.actual
+        )
+    }
+}
+
+/// Keeps the postings of every applied transaction and the debit and credit totals per account.
+#[derive(Debug, Default)]
+pub struct Journal {
+    postings: Vec<Posting>,
+    // Debit and credit totals per account
+    totals: BTreeMap<LedgerAccount, (Decimal, Decimal)>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn postings(&self) -> &[Posting] {
+        &self.postings
+    }
+
+    /// Records the postings of an applied transfer.
+    pub fn post_transfer(&mut self, transfer: &Transfer) {
+        let client = transfer.client();
+        let (debit, credit) = match transfer {
+            Transfer::Deposit(_) => (
+                LedgerAccount::ExternalSettlement,
+                LedgerAccount::ClientAvailable(client),
+            ),
+            Transfer::Withdrawal(_) => (
+                LedgerAccount::ClientAvailable(client),
+                LedgerAccount::ExternalSettlement,
+            ),
+        };
+        self.post(transfer.transaction_id(), debit, credit, transfer.amount());
+    }
+
+    /// Records the postings of a deposit that is held for review.
+    pub fn post_pending(&mut self, deposit: &Deposit) {
+        self.post(
+            deposit.transaction_id(),
+            LedgerAccount::ExternalSettlement,
+            LedgerAccount::ClientPending(deposit.client()),
+            deposit.amount(),
+        );
+    }
+
+    /// Records the postings of a mutation applied to the account of `client`.
+    pub fn post_mutation(&mut self, client: Client, mutation: &Mutation, amount: Decimal) {
+        let (debit, credit) = match mutation {
+            Mutation::Dispute(_) => (
+                LedgerAccount::ClientAvailable(client),
+                LedgerAccount::ClientHeld(client),
+            ),
+            Mutation::Resolve(_) => (
+                LedgerAccount::ClientHeld(client),
+                LedgerAccount::ClientAvailable(client),
+            ),
+            Mutation::ChargeBack(_) => (
+                LedgerAccount::ClientHeld(client),
+                LedgerAccount::ExternalSettlement,
+            ),
+            Mutation::Finalize(_) => return,
+            Mutation::Release(_) => (
+                LedgerAccount::ClientPending(client),
+                LedgerAccount::ClientAvailable(client),
+            ),
+            // The funds of a rejected deposit are returned to the sender
+            Mutation::Reject(_) => (
+                LedgerAccount::ClientPending(client),
+                LedgerAccount::ExternalSettlement,
+            ),
+        };
+        self.post(mutation.transaction_id(), debit, credit, amount);
+    }
+
+    fn post(
+        &mut self,
+        tx: TransactionId,
+        debit: LedgerAccount,
+        credit: LedgerAccount,
+        amount: Decimal,
+    ) {
+        self.totals.entry(debit).or_default().0 += amount;
+        self.totals.entry(credit).or_default().1 += amount;
+        self.postings.push(Posting {
+            tx,
+            debit,
+            credit,
+            amount,
+        });
+    }
+
+    /// Returns the credit balance of the account, which is negative for a net debit.
+    pub fn balance(&self, account: LedgerAccount) -> Decimal {
+        self.totals
+            .get(&account)
+            .map(|(debit, credit)| credit - debit)
+            .unwrap_or_default()
+    }
+
+    /// Compares the available, held and pending funds of the accounts against the journal.
+    pub fn reconcile<'a, I>(&self, accounts: I) -> Vec<Discrepancy>
+    where
+        I: IntoIterator<Item = &'a Account>,
+    {
+        let mut discrepancies = Vec::new();
+        for account in accounts {
+            let client = account.client();
+            for (ledger_account, actual) in [
+                (LedgerAccount::ClientAvailable(client), account.available()),
+                (LedgerAccount::ClientHeld(client), account.held()),
+                (LedgerAccount::ClientPending(client), account.pending()),
+            ] {
+                let expected = self.balance(ledger_account);
+                if expected != actual {
+                    discrepancies.push(Discrepancy {
+                        account: ledger_account,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+        }
+        discrepancies
+    }
+
+    /// Returns the net debit or credit balance of every account followed by the totals.
+    /// The total debits always equal the total credits.
+    pub fn trial_balance(&self) -> Vec<TrialBalanceLine> {
+        let mut total_debit = Decimal::ZERO;
+        let mut total_credit = Decimal::ZERO;
+        let mut lines: Vec<TrialBalanceLine> = self
+            .totals
+            .iter()
+            .map(|(account, (debit, credit))| {
+                let (debit, credit) = if debit > credit {
+                    (debit - credit, Decimal::ZERO)
+                } else {
+                    (Decimal::ZERO, credit - debit)
+                };
+                total_debit += debit;
+                total_credit += credit;
+                TrialBalanceLine {
+                    account: account.to_string(),
+                    debit,
+                    credit,
+                }
+            })
+            .collect();
+        lines.push(TrialBalanceLine {
+            account: "total".to_string(),
+            debit: total_debit,
+            credit: total_credit,
+        });
+        lines
+    }
+
+    /// Writes the trial balance report as csv.
+    pub fn to_csv<W>(&self, w: &mut W) -> Result<(), csv::Error>
+    where
+        W: std::io::Write,
+    {
+        let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(w);
+        self.trial_balance()
+            .iter()
+            .try_for_each(|line| wtr.serialize(line))?;
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::LedgerAccount;
+    use crate::{client::Client, transaction::transactions, trial_balance::TrialBalance};
+
+    #[test]
+    fn test_journal() {
+        let data = "type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.0
+withdrawal,1,3,4.0
+withdrawal,2,4,50.0
+dispute,1,1,
+dispute,2,2,
+resolve,2,2,
+chargeback,1,1,";
+        let mut trial_balance = TrialBalance::new().with_journal();
+        for tx in transactions(data.as_bytes()) {
+            let _ = trial_balance.handle_transaction(tx.unwrap());
+        }
+        let journal = trial_balance.journal().unwrap();
+
+        // The rejected withdrawal is not posted
+        assert_eq!(journal.postings().len(), 7);
+        assert_eq!(
+            journal.balance(LedgerAccount::ClientAvailable(Client::new(1))),
+            Decimal::new(-4, 0)
+        );
+        assert_eq!(
+            journal.balance(LedgerAccount::ClientHeld(Client::new(1))),
+            Decimal::ZERO
+        );
+        assert_eq!(
+            journal.balance(LedgerAccount::ExternalSettlement),
+            Decimal::new(-1, 0)
+        );
+        assert_eq!(journal.reconcile(&trial_balance.snapshot()), vec![]);
+
+        let mut output = Vec::new();
+        journal.to_csv(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "account,debit,credit
+client:1:available,4,0
+client:2:available,0,5
+client:1:held,0,0
+client:2:held,0,0
+external:settlement,1,0
+total,5,5
+"
+        );
+    }
+}
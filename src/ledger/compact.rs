@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use rust_decimal::Decimal;
+
+use super::Ledger;
+use crate::{
+    client::{Client, RawClientId},
+    transaction::{deposit::Deposit, withdrawal::Withdrawal, Timestamp, TransactionId, Transfer},
+    transaction_record::TransactionRecord,
+};
+
+const OCCUPIED: u8 = 1;
+const WITHDRAWAL: u8 = 1 << 1;
+const UNDER_DISPUTE: u8 = 1 << 2;
+const CHARGE_BACKED: u8 = 1 << 3;
+const HAS_TIMESTAMP: u8 = 1 << 4;
+const HAS_DISPUTED_AT: u8 = 1 << 5;
+const HAS_FOREIGN_CLIENT: u8 = 1 << 6;
+const PENDING: u8 = 1 << 7;
+
+// Offsets of the fields that follow the mantissa in the bytes of a record.
+const CLIENT: usize = 28;
+const FOREIGN_CLIENT: usize = CLIENT + size_of::<RawClientId>();
+const SCALE: usize = FOREIGN_CLIENT + size_of::<RawClientId>();
+
+// Bits of the scale and sign of a decimal in its serialized flags.
+const SCALE_MASK: u8 = 0x1f;
+const NEGATIVE: u8 = 0x80;
+
+/// Represents a [`TransactionRecord`] packed into a fixed width of 34 bytes, without its id.
+/// The `wide-ids` feature widens the client ids, which makes it 46 bytes.
+///
+/// The amount is kept as the 96 bit mantissa of the [`Decimal`] with its scale and sign in a single byte,
+/// the kind of transfer and whether the optional fields are set are kept as flags.
+/// The record is aligned to two bytes so no padding is needed next to a `u32` key.
+/// An all zero record marks an empty slot.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C, packed(2))]
+pub struct CompactRecord {
+    timestamp: u64,
+    disputed_at: u64,
+    mantissa: [u8; 12],
+    client: RawClientId,
+    foreign_client: RawClientId,
+    scale: u8,
+    flags: u8,
+}
+
+impl CompactRecord {
+    pub fn pack(record: &TransactionRecord) -> Self {
+        let tx = record.tx();
+        let mut flags = OCCUPIED;
+        let mut set = |flag, on: bool| {
+            if on {
+                flags |= flag;
+            }
+        };
+        set(WITHDRAWAL, matches!(tx, Transfer::Withdrawal(_)));
+        set(UNDER_DISPUTE, record.under_dispute());
+        set(CHARGE_BACKED, record.charge_backed());
+        set(HAS_TIMESTAMP, tx.timestamp().is_some());
+        set(HAS_DISPUTED_AT, record.disputed_at().is_some());
+        set(HAS_FOREIGN_CLIENT, record.foreign_client().is_some());
+        set(PENDING, record.pending());
+
+        // The serialized decimal holds the flags followed by the mantissa, all little endian
+        let amount = tx.amount().serialize();
+        let mut mantissa = [0; 12];
+        mantissa.copy_from_slice(&amount[4..]);
+
+        Self {
+            mantissa,
+            scale: amount[2] & SCALE_MASK | amount[3] & NEGATIVE,
+            timestamp: tx.timestamp().map_or(0, |timestamp| timestamp.as_secs()),
+            disputed_at: record.disputed_at().map_or(0, |at| at.as_secs()),
+            client: tx.client().id(),
+            foreign_client: record.foreign_client().map_or(0, |client| client.id()),
+            flags,
+        }
+    }
+
+    /// Restores the record of the transaction with the id.
+    pub fn unpack(&self, id: TransactionId) -> TransactionRecord {
+        let client = Client::new(self.client);
+        let mut amount = [0; 16];
+        amount[2] = self.scale & SCALE_MASK;
+        amount[3] = self.scale & NEGATIVE;
+        amount[4..].copy_from_slice(&self.mantissa);
+        let amount = Decimal::deserialize(amount);
+        let timestamp = self
+            .has(HAS_TIMESTAMP)
+            .then(|| Timestamp::from_secs(self.timestamp));
+        let tx = if self.has(WITHDRAWAL) {
+            Transfer::Withdrawal(Withdrawal::new(client, id, amount).with_timestamp(timestamp))
+        } else {
+            Transfer::Deposit(Deposit::new(client, id, amount).with_timestamp(timestamp))
+        };
+
+        TransactionRecord::from_parts(
+            tx,
+            self.has(UNDER_DISPUTE),
+            self.has(CHARGE_BACKED),
+            self.has(HAS_DISPUTED_AT)
+                .then(|| Timestamp::from_secs(self.disputed_at)),
+            self.has(HAS_FOREIGN_CLIENT)
+                .then(|| Client::new(self.foreign_client)),
+        )
+        .with_pending(self.has(PENDING))
+    }
+
+    /// Returns the little endian bytes of the record, as stored by the spilling ledger.
+    pub fn to_bytes(&self) -> [u8; size_of::<CompactRecord>()] {
+        let mut bytes = [0; size_of::<CompactRecord>()];
+        bytes[0..8].copy_from_slice(&{ self.timestamp }.to_le_bytes());
+        bytes[8..16].copy_from_slice(&{ self.disputed_at }.to_le_bytes());
+        bytes[16..28].copy_from_slice(&self.mantissa);
+        bytes[CLIENT..FOREIGN_CLIENT].copy_from_slice(&{ self.client }.to_le_bytes());
+        bytes[FOREIGN_CLIENT..SCALE].copy_from_slice(&{ self.foreign_client }.to_le_bytes());
+        bytes[SCALE] = self.scale;
+        bytes[SCALE + 1] = self.flags;
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; size_of::<CompactRecord>()]) -> Self {
+        let mut mantissa = [0; 12];
+        mantissa.copy_from_slice(&bytes[16..28]);
+        Self {
+            timestamp: u64::from_le_bytes(bytes[0..8].try_into().expect("8 bytes")),
+            disputed_at: u64::from_le_bytes(bytes[8..16].try_into().expect("8 bytes")),
+            mantissa,
+            client: RawClientId::from_le_bytes(
+                bytes[CLIENT..FOREIGN_CLIENT]
+                    .try_into()
+                    .expect("client bytes"),
+            ),
+            foreign_client: RawClientId::from_le_bytes(
+                bytes[FOREIGN_CLIENT..SCALE]
+                    .try_into()
+                    .expect("client bytes"),
+            ),
+            scale: bytes[SCALE],
+            flags: bytes[SCALE + 1],
+        }
+    }
+
+    /// Returns whether the record holds a transaction, as opposed to an empty slot.
+    pub fn occupied(&self) -> bool {
+        self.has(OCCUPIED)
+    }
+
+    fn has(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+}
+
+/// Hashes transaction ids with a single multiplication instead of the default SipHash.
+///
+/// This is considerably faster for integer keys, but unlike SipHash it offers no protection
+/// against inputs crafted to collide.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IdHasher(u64);
+
+// Odd constant derived from the golden ratio that spreads consecutive ids over the hash.
+const SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl Hasher for IdHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(SEED);
+        }
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.0 = (self.0 ^ u64::from(n)).wrapping_mul(SEED);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0 ^ n).wrapping_mul(SEED);
+    }
+
+    fn finish(&self) -> u64 {
+        // Fold the well mixed high bits into the low bits that select the bucket
+        self.0 ^ (self.0 >> 32)
+    }
+}
+
+pub type IdBuildHasher = BuildHasherDefault<IdHasher>;
+
+/// Represents a ledger that keeps every record as a [`CompactRecord`] in a hash map keyed by id.
+#[derive(Debug, Default)]
+pub struct CompactLedger {
+    records: HashMap<TransactionId, CompactRecord, IdBuildHasher>,
+}
+
+impl CompactLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            records: HashMap::with_capacity_and_hasher(capacity, IdBuildHasher::default()),
+        }
+    }
+}
+
+impl Ledger for CompactLedger {
+    fn contains(&self, id: TransactionId) -> bool {
+        self.records.contains_key(&id)
+    }
+
+    fn get(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        self.records.get(&id).map(|record| record.unpack(id))
+    }
+
+    fn insert(&mut self, record: TransactionRecord) {
+        self.records
+            .insert(record.tx().transaction_id(), CompactRecord::pack(&record));
+    }
+
+    fn remove(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        self.records.remove(&id).map(|record| record.unpack(id))
+    }
+
+    fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    fn records(&self) -> Box<dyn Iterator<Item = TransactionRecord> + '_> {
+        Box::new(self.records.iter().map(|(id, record)| record.unpack(*id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasher;
+
+    use super::{CompactRecord, IdBuildHasher};
+    use crate::transaction::TransactionId;
+
+    #[test]
+    fn test_compact_record_size() {
+        let size = if cfg!(feature = "wide-ids") { 46 } else { 34 };
+        assert_eq!(std::mem::size_of::<CompactRecord>(), size);
+    }
+
+    #[test]
+    fn test_id_hasher_spreads_low_bits() {
+        // Ids that are multiples of a large power of two still land in different buckets
+        let buckets: std::collections::HashSet<u64> = (0..1024)
+            .map(|i| IdBuildHasher::default().hash_one(TransactionId::new(i << 16)) & 1023)
+            .collect();
+        assert!(buckets.len() > 512);
+    }
+}
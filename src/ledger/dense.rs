@@ -0,0 +1,104 @@
+use super::{CompactLedger, CompactRecord, Ledger};
+use crate::{
+    transaction::{RawTransactionId, TransactionId},
+    transaction_record::TransactionRecord,
+};
+
+// Number of slots the vector can always grow to, regardless of how many of them are occupied.
+const MIN_DENSE_IDS: usize = 1 << 20;
+
+/// Represents a ledger that keeps every record as a [`CompactRecord`] in a vector indexed by id.
+///
+/// This avoids hashing and the overhead of a hash map altogether for inputs with (nearly) sequential ids
+/// starting close to zero. The vector only grows while at least half of its slots would be occupied,
+/// records of ids beyond that are kept in a [`CompactLedger`] instead.
+#[derive(Debug, Default)]
+pub struct DenseLedger {
+    records: Vec<CompactRecord>,
+    len: usize,
+    // Records of ids that are too far beyond the vector to grow it.
+    outliers: CompactLedger,
+}
+
+impl DenseLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            records: Vec::with_capacity(capacity),
+            len: 0,
+            outliers: CompactLedger::new(),
+        }
+    }
+
+    /// Returns the index of the id in the vector, or `None` when its record is kept elsewhere.
+    fn index(&self, id: TransactionId) -> Option<usize> {
+        usize::try_from(id.id())
+            .ok()
+            .filter(|&index| index < self.records.len())
+    }
+}
+
+impl Ledger for DenseLedger {
+    fn contains(&self, id: TransactionId) -> bool {
+        match self.index(id) {
+            Some(index) if self.records[index].occupied() => true,
+            _ => self.outliers.contains(id),
+        }
+    }
+
+    fn get(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        match self.index(id) {
+            Some(index) if self.records[index].occupied() => Some(self.records[index].unpack(id)),
+            _ => self.outliers.get(id),
+        }
+    }
+
+    fn insert(&mut self, record: TransactionRecord) {
+        let id = record.tx().transaction_id();
+        // A record that was kept as an outlier before the vector grew past it stays there
+        if self.outliers.contains(id) {
+            return self.outliers.insert(record);
+        }
+        let index = match (self.index(id), usize::try_from(id.id())) {
+            (Some(index), _) => index,
+            (None, Ok(index)) if index < MIN_DENSE_IDS.max(2 * (self.len + 1)) => {
+                self.records.resize(index + 1, CompactRecord::default());
+                index
+            }
+            _ => return self.outliers.insert(record),
+        };
+        if !self.records[index].occupied() {
+            self.len += 1;
+        }
+        self.records[index] = CompactRecord::pack(&record);
+    }
+
+    fn remove(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        match self.index(id) {
+            Some(index) if self.records[index].occupied() => {
+                let record = std::mem::take(&mut self.records[index]);
+                self.len -= 1;
+                Some(record.unpack(id))
+            }
+            _ => self.outliers.remove(id),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len + self.outliers.len()
+    }
+
+    fn records(&self) -> Box<dyn Iterator<Item = TransactionRecord> + '_> {
+        Box::new(
+            self.records
+                .iter()
+                .enumerate()
+                .filter(|(_, record)| record.occupied())
+                .map(|(index, record)| record.unpack(TransactionId::new(index as RawTransactionId)))
+                .chain(self.outliers.records()),
+        )
+    }
+}
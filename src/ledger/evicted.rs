@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use crate::transaction::{RawTransactionId, TransactionId};
+
+/// Represents a set of transaction ids stored as ranges of consecutive ids.
+///
+/// Ids are mostly evicted in the order they were issued, so the evicted ids of a long input
+/// collapse into a handful of ranges instead of taking up memory per id.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IdRanges {
+    // Inclusive end of every range keyed by its start, ranges never overlap or touch
+    ranges: BTreeMap<RawTransactionId, RawTransactionId>,
+    len: u64,
+}
+
+impl IdRanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, id: TransactionId) -> bool {
+        let id = id.id();
+        self.ranges
+            .range(..=id)
+            .next_back()
+            .is_some_and(|(_, &end)| end >= id)
+    }
+
+    /// Adds the id, returns whether it was not in the set yet.
+    pub fn insert(&mut self, id: TransactionId) -> bool {
+        if self.contains(id) {
+            return false;
+        }
+        let id = id.id();
+        let before = self
+            .ranges
+            .range(..id)
+            .next_back()
+            .filter(|(_, &end)| end.checked_add(1) == Some(id))
+            .map(|(&start, _)| start);
+        let after = id
+            .checked_add(1)
+            .and_then(|next| self.ranges.remove_entry(&next));
+
+        let start = before.unwrap_or(id);
+        let end = after.map_or(id, |(_, end)| end);
+        self.ranges.insert(start, end);
+        self.len += 1;
+        true
+    }
+
+    /// Returns the number of ids in the set.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of ranges the ids are stored as.
+    pub fn ranges(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdRanges;
+    use crate::transaction::{RawTransactionId, TransactionId};
+
+    #[test]
+    fn test_id_ranges() {
+        let mut ids = IdRanges::new();
+        assert!(ids.is_empty());
+        for id in [5, 7, 6, 1, 2, RawTransactionId::MAX, 0] {
+            assert!(ids.insert(TransactionId::new(id)));
+        }
+        assert!(!ids.insert(TransactionId::new(6)));
+
+        assert_eq!(ids.len(), 7);
+        // 0..=2, 5..=7 and RawTransactionId::MAX
+        assert_eq!(ids.ranges(), 3);
+        for id in [0, 1, 2, 5, 6, 7, RawTransactionId::MAX] {
+            assert!(ids.contains(TransactionId::new(id)));
+        }
+        for id in [3, 4, 8, RawTransactionId::MAX - 1] {
+            assert!(!ids.contains(TransactionId::new(id)));
+        }
+
+        let mut ids = IdRanges::new();
+        for id in 0..100_000 {
+            ids.insert(TransactionId::new(id));
+        }
+        assert_eq!(ids.ranges(), 1);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{collections::HashMap, fmt, hash::BuildHasher};
+
+use crate::{transaction::TransactionId, transaction_record::TransactionRecord};
+
+pub use compact::{CompactLedger, CompactRecord, IdBuildHasher, IdHasher};
+pub use dense::DenseLedger;
+pub use evicted::IdRanges;
+pub use spill::SpillLedger;
+
+mod compact;
+mod dense;
+mod evicted;
+mod spill;
+
+/// Represents the storage of the transaction records of a trial balance.
+///
+/// Records are handed out by value, so a ledger is free to store them in any representation.
+/// A record that has been mutated is written back with [`Ledger::insert`].
+pub trait Ledger: fmt::Debug + Send {
+    /// Returns whether the ledger holds a record for the transaction.
+    fn contains(&self, id: TransactionId) -> bool;
+
+    /// Returns a copy of the record of the transaction.
+    fn get(&mut self, id: TransactionId) -> Option<TransactionRecord>;
+
+    /// Stores the record, replacing the record of the same transaction.
+    fn insert(&mut self, record: TransactionRecord);
+
+    /// Removes the record of the transaction and returns it.
+    fn remove(&mut self, id: TransactionId) -> Option<TransactionRecord>;
+
+    /// Returns the number of records in the ledger.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a copy of every record in no particular order.
+    fn records(&self) -> Box<dyn Iterator<Item = TransactionRecord> + '_>;
+}
+
+impl<S> Ledger for HashMap<TransactionId, TransactionRecord, S>
+where
+    S: BuildHasher + Send,
+{
+    fn contains(&self, id: TransactionId) -> bool {
+        self.contains_key(&id)
+    }
+
+    fn get(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        HashMap::get(self, &id).cloned()
+    }
+
+    fn insert(&mut self, record: TransactionRecord) {
+        HashMap::insert(self, record.tx().transaction_id(), record);
+    }
+
+    fn remove(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        HashMap::remove(self, &id)
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn records(&self) -> Box<dyn Iterator<Item = TransactionRecord> + '_> {
+        Box::new(self.values().cloned())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use rust_decimal::Decimal;
+
+    use super::{CompactLedger, DenseLedger, Ledger};
+    use crate::{
+        client::{Client, RawClientId},
+        policy::DisputePolicy,
+        transaction::{
+            deposit::Deposit, dispute::Dispute, withdrawal::Withdrawal, Mutation, Timestamp,
+            TransactionId, Transfer,
+        },
+        transaction_record::TransactionRecord,
+    };
+
+    /// Returns records that together use every field of a record.
+    pub(crate) fn records() -> Vec<TransactionRecord> {
+        let deposit = Transfer::Deposit(
+            Deposit::new(
+                Client::new(1),
+                TransactionId::new(3),
+                Decimal::new(-12345, 4),
+            )
+            .with_timestamp(Some(Timestamp::from_secs(1_700_000_000))),
+        );
+        let withdrawal = Transfer::Withdrawal(Withdrawal::new(
+            Client::new(RawClientId::MAX),
+            TransactionId::new(0),
+            Decimal::MAX,
+        ));
+        let mut disputed = TransactionRecord::new(deposit.clone());
+        let dispute = Dispute::new(Client::new(2), TransactionId::new(3))
+            .with_timestamp(Some(Timestamp::from_secs(1_700_000_100)));
+        disputed
+            .mutate(&Mutation::Dispute(dispute), &DisputePolicy::default())
+            .unwrap();
+
+        vec![
+            TransactionRecord::new(withdrawal),
+            TransactionRecord::new(deposit),
+            disputed,
+        ]
+    }
+
+    pub(crate) fn test_ledger<L: Ledger>(mut ledger: L) {
+        assert!(ledger.is_empty());
+        let records = records();
+        for record in &records {
+            ledger.insert(record.clone());
+        }
+
+        assert_eq!(ledger.len(), 2);
+        assert!(ledger.contains(TransactionId::new(0)));
+        assert!(!ledger.contains(TransactionId::new(1)));
+        assert_eq!(
+            ledger.get(TransactionId::new(0)).as_ref(),
+            Some(&records[0])
+        );
+        assert_eq!(
+            ledger.get(TransactionId::new(3)).as_ref(),
+            Some(&records[2])
+        );
+        assert_eq!(ledger.get(TransactionId::new(5)), None);
+
+        let mut stored: Vec<TransactionRecord> = ledger.records().collect();
+        stored.sort_by_key(|record| record.tx().transaction_id());
+        assert_eq!(stored, [records[0].clone(), records[2].clone()]);
+
+        assert_eq!(
+            ledger.remove(TransactionId::new(0)),
+            Some(records[0].clone())
+        );
+        assert_eq!(ledger.remove(TransactionId::new(0)), None);
+        assert!(!ledger.contains(TransactionId::new(0)));
+        assert_eq!(ledger.len(), 1);
+        assert_eq!(ledger.records().count(), 1);
+    }
+
+    #[test]
+    fn test_ledgers() {
+        test_ledger(HashMap::new());
+        test_ledger(CompactLedger::new());
+        test_ledger(DenseLedger::new());
+    }
+
+    #[test]
+    fn test_dense_ledger_outliers() {
+        let record = |id| {
+            TransactionRecord::new(Transfer::Deposit(Deposit::new(
+                Client::new(1),
+                TransactionId::new(id),
+                Decimal::new(10, 0),
+            )))
+        };
+        // The vector does not grow to an id far beyond the records it holds
+        let mut ledger = DenseLedger::new();
+        for id in [0, 4_000_000_000, 5] {
+            ledger.insert(record(id));
+        }
+        assert_eq!(ledger.len(), 3);
+        assert_eq!(ledger.records().count(), 3);
+        assert!(ledger.contains(TransactionId::new(4_000_000_000)));
+        assert_eq!(
+            ledger.remove(TransactionId::new(4_000_000_000)),
+            Some(record(4_000_000_000))
+        );
+        assert_eq!(ledger.len(), 2);
+    }
+}
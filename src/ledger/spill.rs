@@ -0,0 +1,381 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{CompactRecord, IdBuildHasher, Ledger};
+use crate::{
+    transaction::{RawTransactionId, TransactionId},
+    transaction_record::TransactionRecord,
+};
+
+/// Returns the capacity of a hash map of records that fits in `memory` bytes.
+///
+/// The hash map allocates a power of two buckets for 8/7 of its capacity, every bucket holding
+/// an entry and a control byte; the queue holds up to two ids and sequence numbers per record.
+fn hot_capacity(memory: usize) -> usize {
+    let per_bucket = size_of::<(TransactionId, (CompactRecord, u32))>()
+        + 1
+        + 2 * size_of::<(TransactionId, u32)>();
+    let buckets = memory / per_bucket;
+    if buckets == 0 {
+        return 1;
+    }
+    let buckets = 1 << (usize::BITS - 1 - buckets.leading_zeros());
+    (buckets / 8 * 7).max(1)
+}
+
+/// Represents a ledger that keeps a bounded number of records in memory and spills the rest to a file.
+///
+/// The file is a table of [`CompactRecord`]s at the offset of their id, so no index is needed;
+/// on most file systems the gaps left by unused ids do not take up disk space.
+/// When the records in memory exceed the memory cap, the records that were inserted first are spilled.
+/// A spilled record is moved back into memory when it is read, since it is likely to be mutated.
+/// The ids of the spilled records are kept in a sparse bitmap, which is counted against the memory cap.
+/// The file is removed once the ledger is dropped, on Unix it is unlinked right away.
+///
+/// # Panics
+/// Reading and writing the spill file is not expected to fail; any I/O error panics,
+/// as does an id whose offset in the file does not fit in 64 bits.
+#[derive(Debug)]
+pub struct SpillLedger {
+    // Records in memory with the sequence number of their entry in `order`
+    hot: HashMap<TransactionId, (CompactRecord, u32), IdBuildHasher>,
+    // Ids in memory in the order they were moved there. Entries of ids that have been spilled
+    // or removed since, or whose sequence number no longer matches the record in memory, are stale
+    // and dropped once they make up half of the queue
+    order: VecDeque<(TransactionId, u32)>,
+    sequence: u32,
+    max_hot: usize,
+    memory: usize,
+    // Words of 64 bits, keyed by id / 64, of the ids that are stored in the file
+    spilled: HashMap<RawTransactionId, u64, IdBuildHasher>,
+    spilled_len: usize,
+    file: File,
+    path: PathBuf,
+}
+
+impl SpillLedger {
+    /// Creates a ledger that spills to a new file at `path` once its records take up more than `memory` bytes.
+    pub fn create<P: AsRef<Path>>(path: P, memory: usize) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        // The open file stays usable, and is cleaned up however the process ends
+        #[cfg(unix)]
+        fs::remove_file(&path)?;
+        let capacity = hot_capacity(memory);
+        // Removed records leave tombstones in the map, which are only cleared in place
+        // while the map is at most half full; otherwise the map grows
+        let max_hot = (capacity / 2).max(1);
+        Ok(Self {
+            hot: HashMap::with_capacity_and_hasher(capacity, IdBuildHasher::default()),
+            order: VecDeque::with_capacity(max_hot),
+            sequence: 0,
+            max_hot,
+            memory,
+            spilled: HashMap::default(),
+            spilled_len: 0,
+            file,
+            path,
+        })
+    }
+
+    /// Returns the path of the spill file, which on Unix no longer exists once the ledger is created.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the number of records that are stored in the file.
+    pub fn spilled_len(&self) -> usize {
+        self.spilled_len
+    }
+
+    fn is_spilled(&self, id: TransactionId) -> bool {
+        let id = id.id();
+        self.spilled
+            .get(&(id / 64))
+            .is_some_and(|bits| bits & (1 << (id % 64)) != 0)
+    }
+
+    fn set_spilled(&mut self, id: TransactionId, spilled: bool) {
+        let id = id.id();
+        let bit = 1 << (id % 64);
+        if spilled {
+            let bits = self.spilled.entry(id / 64).or_default();
+            if *bits & bit == 0 {
+                *bits |= bit;
+                self.spilled_len += 1;
+            }
+        } else if let Some(bits) = self.spilled.get_mut(&(id / 64)) {
+            if *bits & bit != 0 {
+                *bits &= !bit;
+                self.spilled_len -= 1;
+                if *bits == 0 {
+                    self.spilled.remove(&(id / 64));
+                }
+            }
+        }
+    }
+
+    /// Returns the number of records that may be kept in memory, leaving room for the bitmap of spilled ids.
+    fn hot_limit(&self) -> usize {
+        let bitmap = self.spilled.capacity() / 7 * 8 * (size_of::<(RawTransactionId, u64)>() + 1);
+        let share = self.memory.saturating_sub(bitmap) as u128;
+        let limit = self.max_hot as u128 * share / self.memory.max(1) as u128;
+        (limit as usize).max(1)
+    }
+
+    fn offset(id: TransactionId) -> u64 {
+        u64::try_from(u128::from(id.id()) * size_of::<CompactRecord>() as u128)
+            .expect("Transaction id beyond the size of the spill file")
+    }
+
+    fn read(&self, id: TransactionId) -> CompactRecord {
+        let mut bytes = [0; size_of::<CompactRecord>()];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(Self::offset(id)))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .expect("Could not read from the spill file");
+        CompactRecord::from_bytes(bytes)
+    }
+
+    fn write(&self, id: TransactionId, record: &CompactRecord) {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(Self::offset(id)))
+            .and_then(|_| file.write_all(&record.to_bytes()))
+            .expect("Could not write to the spill file");
+    }
+
+    /// Spills the records that were moved into memory first until there is room for another one.
+    fn make_room(&mut self) {
+        let limit = self.hot_limit();
+        while self.hot.len() >= limit {
+            let Some((id, sequence)) = self.order.pop_front() else {
+                return;
+            };
+            // A record that was removed and inserted again has a newer entry further back
+            if self.hot.get(&id).is_some_and(|(_, hot)| *hot == sequence) {
+                let (record, _) = self.hot.remove(&id).expect("Record is in memory");
+                self.write(id, &record);
+                self.set_spilled(id, true);
+            }
+        }
+        // Give back the memory the bitmap of spilled ids has taken over
+        if self.hot.capacity() >= limit * 4 {
+            self.hot.shrink_to(limit * 2);
+        }
+    }
+
+    /// Moves a record into memory, spilling other records when needed.
+    fn store_hot(&mut self, id: TransactionId, record: CompactRecord) {
+        if let Some((hot, _)) = self.hot.get_mut(&id) {
+            *hot = record;
+            return;
+        }
+        self.make_room();
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.hot.insert(id, (record, sequence));
+        self.order.push_back((id, sequence));
+        self.set_spilled(id, false);
+        self.drop_stale();
+    }
+
+    /// Drops the stale entries from the order once there are more of them than records in memory,
+    /// so records that are removed before they are spilled do not grow the order.
+    fn drop_stale(&mut self) {
+        if self.order.len() > 2 * self.hot.len() {
+            let hot = &self.hot;
+            self.order
+                .retain(|(id, sequence)| hot.get(id).is_some_and(|(_, hot)| hot == sequence));
+        }
+    }
+}
+
+impl Ledger for SpillLedger {
+    fn contains(&self, id: TransactionId) -> bool {
+        self.hot.contains_key(&id) || self.is_spilled(id)
+    }
+
+    fn get(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        if let Some((record, _)) = self.hot.get(&id) {
+            return Some(record.unpack(id));
+        }
+        if !self.is_spilled(id) {
+            return None;
+        }
+        let record = self.read(id);
+        self.store_hot(id, record);
+        Some(record.unpack(id))
+    }
+
+    fn insert(&mut self, record: TransactionRecord) {
+        let id = record.tx().transaction_id();
+        self.store_hot(id, CompactRecord::pack(&record));
+    }
+
+    fn remove(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        // The id is left in the order of records in memory until the stale entries are dropped
+        if let Some((record, _)) = self.hot.remove(&id) {
+            self.drop_stale();
+            return Some(record.unpack(id));
+        }
+        if !self.is_spilled(id) {
+            return None;
+        }
+        self.set_spilled(id, false);
+        Some(self.read(id).unpack(id))
+    }
+
+    fn len(&self) -> usize {
+        self.hot.len() + self.spilled_len
+    }
+
+    fn records(&self) -> Box<dyn Iterator<Item = TransactionRecord> + '_> {
+        let hot = self.hot.iter().map(|(id, (record, _))| record.unpack(*id));
+        let spilled = self
+            .spilled
+            .iter()
+            .flat_map(|(&word, &bits)| {
+                (0..64)
+                    .filter(move |bit| bits & (1 << bit) != 0)
+                    .map(move |bit| TransactionId::new(word * 64 + bit))
+            })
+            .map(|id| self.read(id).unpack(id));
+        Box::new(hot.chain(spilled))
+    }
+}
+
+#[cfg(not(unix))]
+impl Drop for SpillLedger {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            tracing::error!("Could not remove spill file {:?}: {:?}", self.path, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{hot_capacity, SpillLedger};
+    use crate::{
+        client::Client,
+        ledger::Ledger,
+        policy::DisputePolicy,
+        transaction::{
+            deposit::Deposit, transactions, RawTransactionId, Transaction, TransactionId, Transfer,
+        },
+        transaction_record::TransactionRecord,
+        trial_balance::TrialBalance,
+    };
+
+    #[test]
+    fn test_spill_ledger_matches_memory() {
+        let mut data = String::from("type, client, tx, amount\n");
+        for i in 1..=2000u32 {
+            let client = i % 7;
+            match i % 5 {
+                0..=2 => data.push_str(&format!("deposit, {client}, {i}, {}.25\n", i % 90)),
+                3 => data.push_str(&format!("dispute, {}, {}\n", (i - 3) % 7, i - 3)),
+                _ => data.push_str(&format!("resolve, {}, {}\n", (i - 3) % 7, i / 3)),
+            }
+        }
+        let transactions: Vec<Transaction> =
+            transactions(data.as_bytes()).map(Result::unwrap).collect();
+
+        let path = std::env::temp_dir().join(format!("csv-reader-{}.ledger", std::process::id()));
+        let ledger = SpillLedger::create(&path, 1024).unwrap();
+        let policy = DisputePolicy::default();
+        let mut spilling = TrialBalance::new()
+            .with_dispute_policy(policy)
+            .with_ledger(ledger);
+        let mut memory = TrialBalance::new().with_dispute_policy(policy);
+        for tx in transactions {
+            assert_eq!(
+                spilling.handle_transaction(tx.clone()),
+                memory.handle_transaction(tx)
+            );
+        }
+        assert_eq!(spilling.snapshot(), memory.snapshot());
+        assert_eq!(spilling.verify(), memory.verify());
+        drop(spilling);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_spill_ledger_records() {
+        let path =
+            std::env::temp_dir().join(format!("csv-reader-{}-records.ledger", std::process::id()));
+        let ledger = SpillLedger::create(&path, 0).unwrap();
+        crate::ledger::tests::test_ledger(ledger);
+
+        let mut ledger = SpillLedger::create(&path, 0).unwrap();
+        let records = crate::ledger::tests::records();
+        for record in &records {
+            ledger.insert(record.clone());
+        }
+        assert_eq!(ledger.spilled_len(), 1);
+        assert_eq!(ledger.len(), 2);
+        // Reading the spilled record moves it back into memory
+        let id = records[0].tx().transaction_id();
+        assert_eq!(ledger.get(id).as_ref(), Some(&records[0]));
+        assert_eq!(ledger.spilled_len(), 1);
+        assert!(!ledger.is_spilled(id));
+        assert_eq!(ledger.len(), 2);
+    }
+
+    fn deposit(id: RawTransactionId) -> TransactionRecord {
+        TransactionRecord::new(Transfer::Deposit(Deposit::new(
+            Client::new(1),
+            TransactionId::new(id),
+            Decimal::new(10, 0),
+        )))
+    }
+
+    #[test]
+    fn test_spill_ledger_drops_stale_order() {
+        let path =
+            std::env::temp_dir().join(format!("csv-reader-{}-order.ledger", std::process::id()));
+        let mut ledger = SpillLedger::create(&path, 1 << 20).unwrap();
+        // Records that are removed before they are spilled, like finalized ones, leave no trace
+        for id in 0..10_000 {
+            ledger.insert(deposit(id));
+            if id % 10 != 0 {
+                ledger.remove(TransactionId::new(id));
+            }
+        }
+        assert_eq!(ledger.len(), 1000);
+        assert_eq!(ledger.spilled_len(), 0);
+        assert!(ledger.order.len() <= 2 * ledger.hot.len());
+    }
+
+    #[test]
+    fn test_spill_ledger_skips_stale_order() {
+        let path =
+            std::env::temp_dir().join(format!("csv-reader-{}-stale.ledger", std::process::id()));
+        // Room for three records in memory
+        let memory = (1..).find(|&memory| hot_capacity(memory) / 2 == 3).unwrap();
+        let mut ledger = SpillLedger::create(&path, memory).unwrap();
+        for id in 1..=3 {
+            ledger.insert(deposit(id));
+        }
+        // The record inserted again is the most recent one, so the next oldest is spilled
+        ledger.remove(TransactionId::new(1));
+        ledger.insert(deposit(1));
+        ledger.insert(deposit(4));
+        assert!(!ledger.is_spilled(TransactionId::new(1)));
+        assert!(ledger.is_spilled(TransactionId::new(2)));
+        assert_eq!(ledger.spilled_len(), 1);
+        assert_eq!(ledger.len(), 4);
+        assert_eq!(ledger.get(TransactionId::new(2)), Some(deposit(2)));
+    }
+}
@@ -0,0 +1,24 @@
+pub mod account;
+pub mod account_store;
+pub mod client;
+pub mod error;
+pub mod journal;
+pub mod ledger;
+pub mod metrics;
+pub mod observer;
+pub mod policy;
+pub mod reconcile;
+pub mod rules;
+pub mod server;
+pub mod sharded;
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod statement;
+pub mod stats;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod transaction;
+pub mod transaction_record;
+pub mod trial_balance;
+pub mod verify;
@@ -1,16 +1,37 @@
 use std::fs::File;
 use std::io::BufReader;
-use std::{env, io::Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::{env, io::Write, mem};
 use tracing::{error, info};
 
-use transaction::{transaction_reader, Transaction};
+use cli::{AccountStoreKind, Command, Counterpart, LedgerKind, Options};
+use csv_reader::{
+    account_store::DenseAccountStore,
+    ledger::{CompactLedger, DenseLedger, SpillLedger},
+    reconcile::{self, Balance},
+    rules::{self, RuleSet, Screener},
+    server::Server,
+    sharded::{self, ShardedTrialBalance},
+    snapshot, statement,
+    stats::Stats,
+    transaction::{
+        dialect::{Dialect, Profiles},
+        error::DeserializationError,
+        interner::{self, SharedInterner, TxIds},
+        pipeline::parallel_transactions,
+        resolve,
+        strict::strict_transactions,
+        transactions, validate, Transaction,
+    },
+    trial_balance::TrialBalance,
+};
 
-mod account;
-mod client;
-mod error;
-mod transaction;
-mod transaction_record;
-mod trial_balance;
+#[cfg(feature = "sqlite")]
+use csv_reader::sqlite::SqliteStore;
+
+mod cli;
 
 #[cfg(feature = "logging")]
 fn init_logging() {
@@ -29,46 +50,519 @@ fn init_logging() {
         .init();
 }
 
+// Memory in MiB the spill ledger keeps records in when not configured.
+const DEFAULT_LEDGER_MEMORY: usize = 256;
+
+// Seconds between writes of the metrics file when not configured.
+const DEFAULT_METRICS_INTERVAL: u64 = 15;
+
+// Number of rows after which the state is committed to the database.
+#[cfg(feature = "sqlite")]
+const DB_CHECKPOINT_ROWS: u64 = 10_000;
+
+type Transactions = Box<dyn Iterator<Item = Result<Transaction, DeserializationError>>>;
+
+/// Reads the transactions from the input, parsing them in parallel when configured.
+///
+/// In strict mode every rejected row is reported on stderr, whether or not logging is enabled.
+/// Transaction ids that are strings are interned by the returned interner.
+fn read_transactions(options: &Options) -> (Transactions, Option<SharedInterner>) {
+    // The input of reconcile is a balances file, its transactions come from the counterpart
+    let input = match &options.command {
+        Command::Reconcile(Some(Counterpart::Transactions(path))) => path,
+        Command::Reconcile(_) | Command::Validate => "",
+        _ => &options.input,
+    };
+    if input.is_empty() {
+        return (Box::new(std::iter::empty()), None);
+    }
+    let file = File::open(input).expect("Could not open file");
+    let reader = BufReader::new(file);
+    if options.dialect.is_some() || options.tx_ids != TxIds::Numeric {
+        let mut dialect = match &options.dialect {
+            Some(path) => load_dialect(path, options.profile.as_deref()),
+            None => Dialect::default(),
+        };
+        if options.tx_ids != TxIds::Numeric {
+            dialect.tx_ids = options.tx_ids;
+        }
+        let transactions = dialect.transactions(reader);
+        let interner = transactions.interner();
+        return (Box::new(transactions), interner);
+    }
+    let transactions: Transactions = if options.strict {
+        Box::new(strict_transactions(reader).inspect(|tx| {
+            if let Err(err @ DeserializationError::Strict { .. }) = tx {
+                eprintln!("{err}");
+            }
+        }))
+    } else if options.parse_threads > 1 {
+        Box::new(parallel_transactions(reader, options.parse_threads))
+    } else {
+        Box::new(transactions(reader))
+    };
+    (transactions, None)
+}
+
+/// Writes csv with `write`, replacing the interned transaction ids by the names they were read as.
+fn write_csv<W, F>(interner: Option<&SharedInterner>, mut w: W, write: F) -> Result<(), csv::Error>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> Result<(), csv::Error>,
+{
+    let Some(interner) = interner else {
+        return write(&mut w);
+    };
+    let mut csv = Vec::new();
+    write(&mut csv)?;
+    interner::lock(interner).resolve_csv(csv.as_slice(), &mut w)
+}
+
+/// Handles all transactions, logging the ones that could not be parsed or handled.
+fn process(trial_balance: &mut TrialBalance, transactions: Transactions) {
+    for tx in transactions {
+        handle(trial_balance, tx);
+    }
+}
+
+fn handle(trial_balance: &mut TrialBalance, tx: Result<Transaction, DeserializationError>) {
+    match tx {
+        Ok(tx) => {
+            info!("Handling transaction {:?}", tx);
+            let err = trial_balance.handle_transaction(tx);
+            if let Err(err) = err {
+                error!("Could not handle transaction {:?}", err);
+            }
+        }
+        Err(err) => {
+            error!("Could not parse transaction {:?}", err);
+            trial_balance.record_invalid();
+        }
+    }
+}
+
+/// Handles the transactions like [`process`] while persisting the state in the database,
+/// skipping the rows that an earlier run already handled.
+#[cfg(feature = "sqlite")]
+fn process_with_db(trial_balance: &mut TrialBalance, path: &str, transactions: Transactions) {
+    let store = SqliteStore::open(path).and_then(|store| {
+        let rows = store.rows()?;
+        let ledger = store.ledger()?;
+        let accounts = store.accounts()?;
+        Ok((store, rows, ledger, accounts))
+    });
+    let (mut store, mut rows, ledger, accounts) = store.unwrap_or_else(|err| db_error(path, err));
+    *trial_balance = std::mem::take(trial_balance)
+        .with_ledger(ledger)
+        .with_accounts(accounts);
+    if rows > 0 {
+        info!("Resuming after {} rows", rows);
+    }
+
+    for tx in transactions.skip(rows as usize) {
+        handle(trial_balance, tx);
+        rows += 1;
+        if rows % DB_CHECKPOINT_ROWS == 0 {
+            store
+                .checkpoint(&trial_balance.snapshot(), rows)
+                .unwrap_or_else(|err| db_error(path, err));
+        }
+    }
+    store
+        .checkpoint(&trial_balance.snapshot(), rows)
+        .unwrap_or_else(|err| db_error(path, err));
+}
+
+#[cfg(feature = "sqlite")]
+fn db_error(path: &str, err: rusqlite::Error) -> ! {
+    eprintln!("Could not use database {path}: {err}");
+    std::process::exit(1);
+}
+
+/// Creates a trial balance configured by the options.
+fn new_trial_balance(options: &Options, rules: Option<&RuleSet>) -> TrialBalance {
+    let mut trial_balance = TrialBalance::new()
+        .with_dispute_policy(options.dispute_policy)
+        .with_retention(options.retention);
+    if let Some(rules) = rules {
+        trial_balance = trial_balance.with_screener(Screener::new(rules.clone()));
+    }
+    if options.stats || options.stats_json.is_some() {
+        trial_balance = trial_balance.with_stats();
+    }
+    if let Some(every) = options.verify_every {
+        trial_balance = trial_balance.with_verify_every(every);
+    }
+    if options.accounts == AccountStoreKind::Dense {
+        trial_balance = trial_balance.with_account_store(DenseAccountStore::new());
+    }
+    match options.ledger {
+        LedgerKind::Hash => trial_balance,
+        LedgerKind::Compact => trial_balance.with_ledger(CompactLedger::with_capacity(100000)),
+        LedgerKind::Dense => trial_balance.with_ledger(DenseLedger::with_capacity(100000)),
+        LedgerKind::Spill => trial_balance.with_ledger(spill_ledger(options)),
+    }
+}
+
+/// Creates a spill ledger with its own file, as every shard needs one.
+fn spill_ledger(options: &Options) -> SpillLedger {
+    static FILES: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = options
+        .spill_dir
+        .as_ref()
+        .map_or_else(env::temp_dir, PathBuf::from);
+    let name = format!(
+        "csv-reader-{}-{}.ledger",
+        std::process::id(),
+        FILES.fetch_add(1, Ordering::Relaxed)
+    );
+    let memory = options.ledger_memory.unwrap_or(DEFAULT_LEDGER_MEMORY) * 1024 * 1024;
+    match SpillLedger::create(dir.join(&name), memory) {
+        Ok(ledger) => ledger,
+        Err(err) => {
+            eprintln!("Could not create spill file {name} in {dir:?}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads the balances file, exiting when it can not be read.
+fn read_balances(path: &str) -> Vec<Balance> {
+    let balances = File::open(path)
+        .map_err(csv::Error::from)
+        .and_then(|file| reconcile::balances(BufReader::new(file)));
+    match balances {
+        Ok(balances) => balances,
+        Err(err) => {
+            eprintln!("Could not read balances {path}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads the rules from the file, exiting when they can not be read.
+fn load_rules(path: &str) -> RuleSet {
+    match RuleSet::load(path) {
+        Ok(rules) => rules,
+        Err(err) => {
+            eprintln!("Could not load rules {path}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads the profile from the dialects, which may be left out when there is only one.
+fn load_dialect(path: &str, profile: Option<&str>) -> Dialect {
+    let profiles = Profiles::load(path).unwrap_or_else(|err| {
+        eprintln!("Could not load dialects {path}: {err}");
+        std::process::exit(1);
+    });
+    let dialect = match profile {
+        Some(profile) => profiles
+            .get(profile)
+            .ok_or_else(|| format!("{path} has no profile {profile}")),
+        None if profiles.profiles.len() == 1 => Ok(profiles.profiles.values().next().unwrap()),
+        None => Err(format!(
+            "{path} does not have exactly one profile, choose one with --profile"
+        )),
+    };
+    dialect.cloned().unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    })
+}
+
+/// Writes the transactions the rules fired on in all trial balances to the screening report.
+fn write_screening_report<'a, I>(
+    path: Option<&str>,
+    interner: Option<&SharedInterner>,
+    trial_balances: I,
+) where
+    I: IntoIterator<Item = &'a TrialBalance>,
+{
+    let Some(path) = path else {
+        return;
+    };
+    let lines = trial_balances
+        .into_iter()
+        .filter_map(TrialBalance::screener)
+        .flat_map(Screener::report);
+    let res = File::create(path)
+        .map_err(csv::Error::from)
+        .and_then(|file| write_csv(interner, file, |mut w| rules::to_csv(lines, &mut w)));
+    if let Err(err) = res {
+        eprintln!("Could not write screening report {path}: {err}");
+        std::process::exit(1);
+    }
+}
+
+/// Writes the statistics of the run to stderr and to the JSON file when asked for.
+fn write_stats(stderr: bool, path: Option<&str>, stats: Option<Stats>) {
+    let Some(stats) = stats else {
+        return;
+    };
+    if stderr {
+        eprint!("{stats}");
+    }
+    if let Some(path) = path {
+        let res = File::create(path)
+            .map_err(serde_json::Error::io)
+            .and_then(|file| serde_json::to_writer_pretty(file, &stats));
+        if let Err(err) = res {
+            eprintln!("Could not write statistics {path}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Writes the resolves generated for expired disputes in all trial balances to a csv file.
+fn write_auto_resolved<'a, I>(
+    path: Option<&str>,
+    interner: Option<&SharedInterner>,
+    trial_balances: I,
+) where
+    I: IntoIterator<Item = &'a TrialBalance>,
+{
+    let Some(path) = path else {
+        return;
+    };
+    let resolves = trial_balances
+        .into_iter()
+        .flat_map(TrialBalance::auto_resolved);
+    let res = File::create(path)
+        .map_err(csv::Error::from)
+        .and_then(|file| write_csv(interner, file, |mut w| resolve::to_csv(resolves, &mut w)));
+    if let Err(err) = res {
+        eprintln!("Could not write auto resolves {path}: {err}");
+        std::process::exit(1);
+    }
+}
+
+/// Writes the statistics and reports of the run and checks the invariants when asked to.
+///
+/// Every command that handles transactions ends with this, also when it exits with a mismatch.
+fn finish<'a, I>(
+    options: &Options,
+    interner: Option<&SharedInterner>,
+    stats: Option<Stats>,
+    trial_balances: I,
+) where
+    I: IntoIterator<Item = &'a TrialBalance> + Copy,
+{
+    write_stats(options.stats, options.stats_json.as_deref(), stats);
+    write_screening_report(
+        options.screening_report.as_deref(),
+        interner,
+        trial_balances,
+    );
+    write_auto_resolved(options.auto_resolved.as_deref(), interner, trial_balances);
+    if options.verify || options.verify_every.is_some() {
+        exit_on_violations(trial_balances);
+    }
+}
+
+/// Reports the violations of all trial balances and exits when any invariant is violated.
+fn exit_on_violations<'a, I>(trial_balances: I)
+where
+    I: IntoIterator<Item = &'a TrialBalance>,
+{
+    let mut violated = false;
+    for trial_balance in trial_balances {
+        if let Err(violations) = trial_balance.verify() {
+            for violation in violations {
+                eprintln!("{violation}");
+            }
+            violated = true;
+        }
+    }
+    if violated {
+        std::process::exit(3);
+    }
+}
+
 fn main() {
     #[cfg(feature = "logging")]
     init_logging();
 
     info!("Starting the program");
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <transactions_file>", args[0]);
+    let mut options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\n{}", cli::USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    #[cfg(not(feature = "sqlite"))]
+    if options.db.is_some() {
+        eprintln!("--db requires csv-reader to be built with the sqlite feature");
         std::process::exit(1);
     }
 
-    let file = File::open(&args[1]).expect("Could not open file");
-    let reader = BufReader::new(file);
+    let rules = options.rules.as_deref().map(load_rules);
+    let (transactions, interner) = read_transactions(&options);
+    // A profile can choose string ids as well, their interned numbers would differ on resume
+    if options.db.is_some() && interner.is_some() {
+        eprintln!("--db requires a profile with numeric transaction ids");
+        std::process::exit(1);
+    }
+
+    let mut trial_balance = new_trial_balance(&options, rules.as_ref());
 
-    let mut trial_balance = trial_balance::TrialBalance::new();
+    let stdout = std::io::stdout();
+    let mut locked_stdout = stdout.lock();
 
-    let transaction_reader = transaction_reader(reader);
-    for tx_row in transaction_reader {
-        match tx_row {
-            Ok(tx_row) => {
-                let tx: Result<Transaction, _> = tx_row.try_into();
+    // The options stay whole for the final outputs of the command
+    match mem::take(&mut options.command) {
+        Command::Process if options.threads > 1 => {
+            let mut sharded = ShardedTrialBalance::new(options.threads, || {
+                new_trial_balance(&options, rules.as_ref())
+            });
+            let mut invalid = Stats::new();
+            for tx in transactions {
                 match tx {
-                    Ok(tx) => {
-                        info!("Handling transaction {:?}", tx);
-                        let err = trial_balance.handle_transaction(tx);
-                        if let Err(err) = err {
-                            error!("Could not handle transaction {:?}", err);
-                        }
+                    Ok(tx) => sharded.handle_transaction(tx),
+                    Err(err) => {
+                        error!("Could not parse transaction {:?}", err);
+                        invalid.record_invalid();
                     }
-                    Err(err) => error!("Could not parse transaction {:?}", err),
                 }
             }
-            Err(err) => error!("Could not parse transaction {:?}", err),
+            let shards = sharded.finish();
+            if let Err(err) = sharded::to_csv(&shards, &mut locked_stdout) {
+                error!("Could not write to stdout {:?}", err);
+            }
+            locked_stdout.flush().unwrap();
+            let stats = (options.stats || options.stats_json.is_some()).then(|| {
+                let mut stats = invalid;
+                for shard in shards.iter().filter_map(TrialBalance::stats) {
+                    stats.merge(&shard);
+                }
+                stats
+            });
+            finish(&options, interner.as_ref(), stats, &shards);
+            return;
+        }
+        Command::Process => {
+            match options.db.as_deref() {
+                #[cfg(feature = "sqlite")]
+                Some(path) => process_with_db(&mut trial_balance, path, transactions),
+                _ => process(&mut trial_balance, transactions),
+            }
+            info!(
+                "Automatically resolved {} disputes",
+                trial_balance.auto_resolved().len()
+            );
+            if let Err(err) = trial_balance.to_csv(&mut locked_stdout) {
+                error!("Could not write to stdout {:?}", err);
+            }
+        }
+        Command::BalancesAt(cutoffs) => {
+            let snapshots = snapshot::balances_at(&mut trial_balance, transactions, &cutoffs);
+            if let Err(err) = snapshot::to_csv(&snapshots, &mut locked_stdout) {
+                error!("Could not write to stdout {:?}", err);
+            }
+        }
+        Command::Statement(client) => {
+            let client = client.expect("Statement requires a client");
+            trial_balance = trial_balance.with_history();
+            process(&mut trial_balance, transactions);
+            let lines = trial_balance.statement(client).unwrap_or_default();
+            let res = write_csv(interner.as_ref(), &mut locked_stdout, |mut w| {
+                statement::to_csv(lines, &mut w)
+            });
+            if let Err(err) = res {
+                error!("Could not write to stdout {:?}", err);
+            }
+        }
+        Command::Serve(addr) => {
+            process(&mut trial_balance, transactions);
+            let mut server = match Server::bind(&addr, trial_balance) {
+                Ok(server) => server,
+                Err(err) => {
+                    eprintln!("Could not listen on {addr}: {err}");
+                    std::process::exit(1);
+                }
+            };
+            if let Some(path) = options.metrics_file.as_deref() {
+                let interval = options.metrics_interval.unwrap_or(DEFAULT_METRICS_INTERVAL);
+                server =
+                    server.with_metrics_file(PathBuf::from(path), Duration::from_secs(interval));
+            }
+            info!("Listening on {}", addr);
+            if let Err(err) = server.run() {
+                eprintln!("Could not accept connections: {err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Command::Validate => {
+            let file = File::open(&options.input).expect("Could not open file");
+            let report = validate::validate(BufReader::new(file));
+            for finding in &report.findings {
+                writeln!(locked_stdout, "{finding}").expect("Could not write to stdout");
+            }
+            write!(locked_stdout, "{report}").expect("Could not write to stdout");
+            locked_stdout.flush().unwrap();
+            if !report.is_clean() {
+                std::process::exit(2);
+            }
+        }
+        Command::Reconcile(counterpart) => {
+            let left = read_balances(&options.input);
+            let right = match counterpart.expect("Reconcile requires a counterpart") {
+                Counterpart::Balances(path) => read_balances(&path),
+                Counterpart::Transactions(_) => {
+                    process(&mut trial_balance, transactions);
+                    trial_balance.snapshot().iter().map(Balance::of).collect()
+                }
+            };
+            let mismatches = reconcile::reconcile(&left, &right, options.tolerance);
+            for mismatch in &mismatches {
+                writeln!(locked_stdout, "{mismatch}").expect("Could not write to stdout");
+            }
+            locked_stdout.flush().unwrap();
+            if !mismatches.is_empty() {
+                finish(
+                    &options,
+                    interner.as_ref(),
+                    trial_balance.stats(),
+                    [&trial_balance],
+                );
+                eprintln!("Mismatches found: {}", mismatches.len());
+                std::process::exit(2);
+            }
+        }
+        Command::Journal => {
+            trial_balance = trial_balance.with_journal();
+            process(&mut trial_balance, transactions);
+            let journal = trial_balance.journal().expect("Journal is enabled");
+            if let Err(err) = journal.to_csv(&mut locked_stdout) {
+                error!("Could not write to stdout {:?}", err);
+            }
+            let discrepancies = journal.reconcile(&trial_balance.snapshot());
+            if !discrepancies.is_empty() {
+                for discrepancy in discrepancies {
+                    eprintln!("{discrepancy}");
+                }
+                locked_stdout.flush().unwrap();
+                finish(
+                    &options,
+                    interner.as_ref(),
+                    trial_balance.stats(),
+                    [&trial_balance],
+                );
+                std::process::exit(2);
+            }
         }
-    }
-    let stdout = std::io::stdout();
-    let mut locked_stdout = stdout.lock();
-    if let Err(err) = trial_balance.to_csv(&mut locked_stdout) {
-        error!("Could not write to stdout {:?}", err);
     }
     locked_stdout.flush().unwrap();
+    finish(
+        &options,
+        interner.as_ref(),
+        trial_balance.stats(),
+        [&trial_balance],
+    );
 }
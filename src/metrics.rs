@@ -0,0 +1,237 @@
+use std::{fmt::Write as _, fs, io, path::Path, time::Duration};
+
+use rust_decimal::Decimal;
+
+use crate::{account::round, trial_balance::TrialBalance};
+
+// Upper bounds in seconds of the buckets of the latency histogram.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.000_005, 0.000_01, 0.000_025, 0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005,
+    0.01, 0.1,
+];
+
+/// Represents a histogram of durations with fixed buckets, like a Prometheus histogram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    // Number of observations per bucket, not cumulative. The last bucket is `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: [0; LATENCY_BUCKETS.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS.partition_point(|&bound| bound < seconds);
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Renders the metrics of the trial balance in the Prometheus text exposition format.
+///
+/// Counters per transaction type and outcome come from the statistics of the trial balance, so they are
+/// only written when [`TrialBalance::with_stats`] is enabled. `latency` holds the time it took to handle
+/// each transaction.
+pub fn render(trial_balance: &TrialBalance, latency: &Histogram) -> String {
+    let mut out = String::new();
+    // Writing to a string can not fail
+    let _ = write_metrics(&mut out, trial_balance, latency);
+    out
+}
+
+fn write_metrics(
+    out: &mut String,
+    trial_balance: &TrialBalance,
+    latency: &Histogram,
+) -> std::fmt::Result {
+    if let Some(stats) = trial_balance.collected_stats() {
+        header(
+            out,
+            "transactions_total",
+            "counter",
+            "Transactions handled per type and outcome",
+        )?;
+        for (name, stats) in &stats.types {
+            for (outcome, count) in [("applied", stats.applied), ("rejected", stats.rejected)] {
+                writeln!(
+                    out,
+                    "csv_reader_transactions_total{{type=\"{name}\",outcome=\"{outcome}\"}} {count}"
+                )?;
+            }
+        }
+        header(
+            out,
+            "transaction_errors_total",
+            "counter",
+            "Rejected transactions per type and error",
+        )?;
+        for (name, stats) in &stats.types {
+            for (error, count) in &stats.errors {
+                writeln!(
+                    out,
+                    "csv_reader_transaction_errors_total{{type=\"{name}\",error=\"{error}\"}} {count}"
+                )?;
+            }
+        }
+        header(
+            out,
+            "invalid_rows_total",
+            "counter",
+            "Rows that could not be read as a transaction",
+        )?;
+        writeln!(out, "csv_reader_invalid_rows_total {}", stats.invalid)?;
+    }
+
+    // The gauges are taken in a single pass over the accounts, while the service is kept waiting
+    let (mut accounts, mut locked, mut held) = (0, 0, Decimal::ZERO);
+    for account in trial_balance.accounts() {
+        accounts += 1;
+        locked += usize::from(account.locked());
+        held += account.held();
+    }
+    header(out, "accounts", "gauge", "Number of accounts")?;
+    writeln!(out, "csv_reader_accounts {accounts}")?;
+    header(out, "locked_accounts", "gauge", "Number of locked accounts")?;
+    writeln!(out, "csv_reader_locked_accounts {locked}")?;
+    header(
+        out,
+        "ledger_records",
+        "gauge",
+        "Number of transaction records in the ledger",
+    )?;
+    writeln!(
+        out,
+        "csv_reader_ledger_records {}",
+        trial_balance.ledger_len()
+    )?;
+    header(
+        out,
+        "held_funds",
+        "gauge",
+        "Total funds held by open disputes",
+    )?;
+    writeln!(out, "csv_reader_held_funds {}", round(held))?;
+    header(
+        out,
+        "invariant_violations",
+        "gauge",
+        "Violations found by the most recent periodic verification",
+    )?;
+    writeln!(
+        out,
+        "csv_reader_invariant_violations {}",
+        trial_balance.violations().len()
+    )?;
+
+    header(
+        out,
+        "transaction_duration_seconds",
+        "histogram",
+        "Time taken to handle a transaction",
+    )?;
+    let mut cumulative = 0;
+    for (bound, count) in LATENCY_BUCKETS.iter().zip(latency.buckets) {
+        cumulative += count;
+        writeln!(
+            out,
+            "csv_reader_transaction_duration_seconds_bucket{{le=\"{bound}\"}} {cumulative}"
+        )?;
+    }
+    writeln!(
+        out,
+        "csv_reader_transaction_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+        latency.count
+    )?;
+    writeln!(
+        out,
+        "csv_reader_transaction_duration_seconds_sum {}",
+        latency.sum
+    )?;
+    writeln!(
+        out,
+        "csv_reader_transaction_duration_seconds_count {}",
+        latency.count
+    )
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> std::fmt::Result {
+    writeln!(out, "# HELP csv_reader_{name} {help}")?;
+    writeln!(out, "# TYPE csv_reader_{name} {kind}")
+}
+
+/// Writes the metrics to a file for the textfile collector of the node exporter.
+/// The file is replaced atomically, so the collector never reads a partial file.
+pub fn write_textfile(path: &Path, metrics: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, metrics)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{render, write_textfile, Histogram};
+    use crate::{transaction::transactions, trial_balance::TrialBalance};
+
+    #[test]
+    fn test_render() {
+        let data = "type, client, tx, amount
+            deposit, 1, 1, 10
+            deposit, 2, 2, 5
+            withdrawal, 2, 3, 20
+            dispute, 1, 1,";
+        let mut trial_balance = TrialBalance::new().with_stats();
+        for tx in transactions(data.as_bytes()) {
+            let _ = trial_balance.handle_transaction(tx.unwrap());
+        }
+        let mut latency = Histogram::new();
+        latency.observe(Duration::from_micros(3));
+        latency.observe(Duration::from_micros(30));
+        latency.observe(Duration::from_secs(1));
+
+        let metrics = render(&trial_balance, &latency);
+        for line in [
+            "# TYPE csv_reader_transactions_total counter",
+            "csv_reader_transactions_total{type=\"deposit\",outcome=\"applied\"} 2",
+            "csv_reader_transactions_total{type=\"withdrawal\",outcome=\"rejected\"} 1",
+            "csv_reader_transaction_errors_total{type=\"withdrawal\",error=\"insufficient_funds\"} 1",
+            "csv_reader_invalid_rows_total 0",
+            "csv_reader_accounts 2",
+            "csv_reader_locked_accounts 0",
+            "csv_reader_ledger_records 3",
+            "csv_reader_held_funds 10",
+            "csv_reader_invariant_violations 0",
+            "csv_reader_transaction_duration_seconds_bucket{le=\"0.000005\"} 1",
+            "csv_reader_transaction_duration_seconds_bucket{le=\"0.00005\"} 2",
+            "csv_reader_transaction_duration_seconds_bucket{le=\"0.1\"} 2",
+            "csv_reader_transaction_duration_seconds_bucket{le=\"+Inf\"} 3",
+            "csv_reader_transaction_duration_seconds_count 3",
+        ] {
+            assert!(metrics.lines().any(|l| l == line), "{line} in {metrics}");
+        }
+        assert!(!render(&TrialBalance::new(), &latency).contains("transactions_total"));
+
+        let path = std::env::temp_dir().join(format!("csv-reader-{}.prom", std::process::id()));
+        write_textfile(&path, &metrics).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), metrics);
+        std::fs::remove_file(path).unwrap();
+    }
+}
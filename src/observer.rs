@@ -0,0 +1,356 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
+
+use crate::{
+    account::Account,
+    client::Client,
+    error::TransactionError,
+    transaction::{Mutation, TransactionId},
+};
+
+/// Represents the balances of an account at a moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Balances {
+    pub available: Decimal,
+    pub held: Decimal,
+    pub pending: Decimal,
+    pub locked: bool,
+}
+
+impl Balances {
+    pub fn of(account: &Account) -> Self {
+        Self {
+            available: account.available(),
+            held: account.held(),
+            pending: account.pending(),
+            locked: account.locked(),
+        }
+    }
+
+    pub fn total(&self) -> Decimal {
+        self.available + self.held
+    }
+}
+
+/// Represents an outcome of a transaction that observers are notified of.
+///
+/// `amount` is the amount of the transfer, also for the mutations of a transfer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A deposit or withdrawal was applied to the account.
+    TransferApplied {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// A deposit or withdrawal was rejected and left the account unchanged.
+    TransferRejected {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        error: TransactionError,
+    },
+    /// A deposit was held for review and its funds were put in the pending bucket.
+    TransferPending {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The funds of a transfer were held for a dispute.
+    DisputeOpened {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// A dispute was resolved and its funds released, also when resolved automatically.
+    DisputeResolved {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The held funds of a dispute were charged back.
+    ChargedBack {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The pending funds of a deposit were credited after review.
+    PendingReleased {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The pending funds of a deposit were discarded after review.
+    PendingRejected {
+        client: Client,
+        tx: TransactionId,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    },
+    /// The account was locked by the charge back of the transaction.
+    AccountLocked { client: Client, tx: TransactionId },
+}
+
+impl Event {
+    /// Returns the event of an applied mutation, finalizing a transaction has no event.
+    pub(crate) fn mutation(
+        mutation: &Mutation,
+        amount: Decimal,
+        before: Balances,
+        after: Balances,
+    ) -> Option<Self> {
+        let (client, tx) = (mutation.client(), mutation.transaction_id());
+        match mutation {
+            Mutation::Dispute(_) => Some(Event::DisputeOpened {
+                client,
+                tx,
+                amount,
+                before,
+                after,
+            }),
+            Mutation::Resolve(_) => Some(Event::DisputeResolved {
+                client,
+                tx,
+                amount,
+                before,
+                after,
+            }),
+            Mutation::ChargeBack(_) => Some(Event::ChargedBack {
+                client,
+                tx,
+                amount,
+                before,
+                after,
+            }),
+            Mutation::Finalize(_) => None,
+            Mutation::Release(_) => Some(Event::PendingReleased {
+                client,
+                tx,
+                amount,
+                before,
+                after,
+            }),
+            Mutation::Reject(_) => Some(Event::PendingRejected {
+                client,
+                tx,
+                amount,
+                before,
+                after,
+            }),
+        }
+    }
+
+    pub fn client(&self) -> Client {
+        match self {
+            Event::TransferApplied { client, .. }
+            | Event::TransferRejected { client, .. }
+            | Event::TransferPending { client, .. }
+            | Event::PendingReleased { client, .. }
+            | Event::PendingRejected { client, .. }
+            | Event::DisputeOpened { client, .. }
+            | Event::DisputeResolved { client, .. }
+            | Event::ChargedBack { client, .. }
+            | Event::AccountLocked { client, .. } => *client,
+        }
+    }
+
+    pub fn transaction_id(&self) -> TransactionId {
+        match self {
+            Event::TransferApplied { tx, .. }
+            | Event::TransferRejected { tx, .. }
+            | Event::TransferPending { tx, .. }
+            | Event::PendingReleased { tx, .. }
+            | Event::PendingRejected { tx, .. }
+            | Event::DisputeOpened { tx, .. }
+            | Event::DisputeResolved { tx, .. }
+            | Event::ChargedBack { tx, .. }
+            | Event::AccountLocked { tx, .. } => *tx,
+        }
+    }
+}
+
+/// Represents a hook that is called with the outcome of every transaction a trial balance handles.
+///
+/// Observers are called synchronously while the transaction is handled, so they should not block.
+/// Any closure taking an [`Event`] is an observer.
+pub trait Observer: Send {
+    fn notify(&mut self, event: &Event);
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&Event) + Send,
+{
+    fn notify(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Represents the observers registered on a trial balance, notified in the order they were added.
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Box<dyn Observer>>);
+
+impl Observers {
+    pub(crate) fn push(&mut self, observer: Box<dyn Observer>) {
+        self.0.push(observer);
+    }
+
+    /// Returns whether any observer is registered, so events are only built when needed.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn notify(&mut self, event: Event) {
+        for observer in &mut self.0 {
+            observer.notify(&event);
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use rust_decimal::Decimal;
+
+    use super::{Balances, Event};
+    use crate::{
+        client::Client,
+        error::TransactionError,
+        policy::DisputePolicy,
+        transaction::{transactions, TransactionId},
+        trial_balance::TrialBalance,
+    };
+
+    #[test]
+    fn test_observers() {
+        let data = "type, client, tx, amount, timestamp
+            deposit, 1, 1, 10, 0
+            withdrawal, 1, 2, 15, 0
+            dispute, 1, 1, , 10
+            deposit, 2, 3, 5, 10
+            dispute, 2, 3, , 10
+            chargeback, 2, 3, , 20
+            withdrawal, 2, 4, 1, 20
+            withdrawal, 2, 4, 1, 20";
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut trial_balance = {
+            let (events, count) = (Arc::clone(&events), Arc::clone(&count));
+            TrialBalance::new()
+                .with_dispute_policy(DisputePolicy::new().with_auto_resolve_after(100))
+                .with_observer(move |event: &Event| events.lock().unwrap().push(event.clone()))
+                .with_observer(move |_: &Event| {
+                    count.fetch_add(1, Ordering::Relaxed);
+                })
+        };
+        for tx in transactions(data.as_bytes()).map(Result::unwrap) {
+            let _ = trial_balance.handle_transaction(tx);
+        }
+        trial_balance.expire_disputes(crate::transaction::Timestamp::from_secs(110));
+
+        let balances = |available: i64, held: i64, locked: bool| Balances {
+            available: Decimal::new(available, 0),
+            held: Decimal::new(held, 0),
+            pending: Decimal::ZERO,
+            locked,
+        };
+        let (one, two) = (Client::new(1), Client::new(2));
+        let ten = Decimal::new(10, 0);
+        let five = Decimal::new(5, 0);
+        let tx = TransactionId::new;
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                Event::TransferApplied {
+                    client: one,
+                    tx: tx(1),
+                    amount: ten,
+                    before: balances(0, 0, false),
+                    after: balances(10, 0, false),
+                },
+                Event::TransferRejected {
+                    client: one,
+                    tx: tx(2),
+                    amount: Decimal::new(15, 0),
+                    error: TransactionError::InsufficientFunds,
+                },
+                Event::DisputeOpened {
+                    client: one,
+                    tx: tx(1),
+                    amount: ten,
+                    before: balances(10, 0, false),
+                    after: balances(0, 10, false),
+                },
+                Event::TransferApplied {
+                    client: two,
+                    tx: tx(3),
+                    amount: five,
+                    before: balances(0, 0, false),
+                    after: balances(5, 0, false),
+                },
+                Event::DisputeOpened {
+                    client: two,
+                    tx: tx(3),
+                    amount: five,
+                    before: balances(5, 0, false),
+                    after: balances(0, 5, false),
+                },
+                Event::ChargedBack {
+                    client: two,
+                    tx: tx(3),
+                    amount: five,
+                    before: balances(0, 5, false),
+                    after: balances(0, 0, true),
+                },
+                Event::AccountLocked {
+                    client: two,
+                    tx: tx(3),
+                },
+                Event::TransferRejected {
+                    client: two,
+                    tx: tx(4),
+                    amount: Decimal::new(1, 0),
+                    error: TransactionError::AccountLocked,
+                },
+                Event::TransferRejected {
+                    client: two,
+                    tx: tx(4),
+                    amount: Decimal::new(1, 0),
+                    error: TransactionError::DuplicateTransaction(tx(4)),
+                },
+                Event::DisputeResolved {
+                    client: one,
+                    tx: tx(1),
+                    amount: ten,
+                    before: balances(0, 10, false),
+                    after: balances(10, 0, false),
+                },
+            ]
+        );
+        assert_eq!(count.load(Ordering::Relaxed), 10);
+    }
+}
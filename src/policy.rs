@@ -0,0 +1,81 @@
+/// Configures the time limits that apply to disputes.
+///
+/// Both limits are only enforced when the involved transactions carry a timestamp.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DisputePolicy {
+    /// Maximum age in seconds of a transfer that can still be disputed.
+    window: Option<u64>,
+    /// Number of seconds after which an open dispute is resolved automatically.
+    auto_resolve_after: Option<u64>,
+}
+
+impl DisputePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects disputes filed more than `secs` seconds after the original transfer.
+    pub fn with_window(mut self, secs: u64) -> Self {
+        self.window = Some(secs);
+        self
+    }
+
+    /// Resolves disputes that are still open `secs` seconds after they were filed.
+    pub fn with_auto_resolve_after(mut self, secs: u64) -> Self {
+        self.auto_resolve_after = Some(secs);
+        self
+    }
+
+    pub fn window(&self) -> Option<u64> {
+        self.window
+    }
+
+    pub fn auto_resolve_after(&self) -> Option<u64> {
+        self.auto_resolve_after
+    }
+}
+
+/// Configures when the records of transfers are evicted from the ledger of a trial balance.
+///
+/// An evicted transfer can no longer be mutated, its id is still remembered so duplicates are rejected.
+/// Records under dispute are kept until the dispute is settled and charged back records are never evicted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Age in seconds after which a transfer is evicted.
+    max_age: Option<u64>,
+    /// Number of records after which the oldest records are evicted.
+    max_records: Option<usize>,
+}
+
+impl RetentionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evicts transfers that are more than `secs` seconds older than the latest transaction.
+    ///
+    /// Only applies to transfers that carry a timestamp.
+    pub fn with_max_age(mut self, secs: u64) -> Self {
+        self.max_age = Some(secs);
+        self
+    }
+
+    /// Evicts the oldest transfers once the ledger holds more than `n` records.
+    pub fn with_max_records(mut self, n: usize) -> Self {
+        self.max_records = Some(n);
+        self
+    }
+
+    pub fn max_age(&self) -> Option<u64> {
+        self.max_age
+    }
+
+    pub fn max_records(&self) -> Option<usize> {
+        self.max_records
+    }
+
+    /// Returns whether records are ever evicted other than by finalizing them.
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_records.is_some()
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fmt,
+};
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::{
+    account::{round, Account},
+    client::Client,
+};
+
+/// Represents a line of a balances file as written by [`TrialBalance::to_csv`].
+///
+/// Files written before pending funds existed do not have the `pending` column, it reads as zero.
+///
+/// [`TrialBalance::to_csv`]: crate::trial_balance::TrialBalance::to_csv
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Balance {
+    pub client: Client,
+    pub available: Decimal,
+    pub held: Decimal,
+    #[serde(default)]
+    pub pending: Decimal,
+    pub total: Decimal,
+    pub locked: bool,
+}
+
+impl Balance {
+    /// Returns the balance of the account as it is written, rounded to four decimals.
+    pub fn of(account: &Account) -> Self {
+        Self {
+            client: account.client(),
+            available: round(account.available()),
+            held: round(account.held()),
+            pending: round(account.pending()),
+            total: round(account.total()),
+            locked: account.locked(),
+        }
+    }
+
+    fn columns(&self) -> [(&'static str, Decimal); 4] {
+        [
+            ("available", self.available),
+            ("held", self.held),
+            ("pending", self.pending),
+            ("total", self.total),
+        ]
+    }
+}
+
+/// Reads the balances from a file in the format of [`TrialBalance::to_csv`].
+///
+/// [`TrialBalance::to_csv`]: crate::trial_balance::TrialBalance::to_csv
+pub fn balances<R>(reader: R) -> Result<Vec<Balance>, csv::Error>
+where
+    R: std::io::Read,
+{
+    csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .into_deserialize()
+        .collect()
+}
+
+/// Represents one of the two sides that are reconciled, the first file is the left side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// Represents a difference between the balances of the two sides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The client has no balance on the side.
+    MissingClient { client: Client, side: Side },
+    /// The client has more than one balance on the side, only the first one is compared.
+    DuplicateClient { client: Client, side: Side },
+    /// The column differs by more than the tolerance.
+    Value {
+        client: Client,
+        column: &'static str,
+        left: Decimal,
+        right: Decimal,
+    },
+    /// The account is locked on one side only.
+    Locked {
+        client: Client,
+        left: bool,
+        right: bool,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::MissingClient { client, side } => {
+                write!(f, "Client {}: missing on the {} side", client.id(), side)
+            }
+            Mismatch::DuplicateClient { client, side } => write!(
+                f,
+                "Client {}: listed more than once on the {} side",
+                client.id(),
+                side
+            ),
+            Mismatch::Value {
+                client,
+                column,
+                left,
+                right,
+            } => write!(
+                f,
+                "Client {}: {} {} on the left does not match {} on the right",
+                client.id(),
+                column,
+                left,
+                right
+            ),
+            Mismatch::Locked {
+                client,
+                left,
+                right,
+            } => write!(
+                f,
+                "Client {}: locked {} on the left does not match {} on the right",
+                client.id(),
+                left,
+                right
+            ),
+        }
+    }
+}
+
+/// Matches the balances of both sides by client and returns every difference, ordered by client.
+///
+/// Amounts match when they differ by no more than `tolerance`.
+pub fn reconcile(left: &[Balance], right: &[Balance], tolerance: Decimal) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut index = |balances: &[Balance], side| {
+        let mut by_client = BTreeMap::new();
+        for balance in balances {
+            match by_client.entry(balance.client) {
+                Entry::Vacant(entry) => {
+                    entry.insert(*balance);
+                }
+                Entry::Occupied(_) => mismatches.push(Mismatch::DuplicateClient {
+                    client: balance.client,
+                    side,
+                }),
+            }
+        }
+        by_client
+    };
+    let (left, right) = (index(left, Side::Left), index(right, Side::Right));
+
+    let clients: BTreeSet<Client> = left.keys().chain(right.keys()).copied().collect();
+    for client in clients {
+        let (left, right) = match (left.get(&client), right.get(&client)) {
+            (Some(left), Some(right)) => (left, right),
+            (None, _) => {
+                mismatches.push(Mismatch::MissingClient {
+                    client,
+                    side: Side::Left,
+                });
+                continue;
+            }
+            (_, None) => {
+                mismatches.push(Mismatch::MissingClient {
+                    client,
+                    side: Side::Right,
+                });
+                continue;
+            }
+        };
+        for ((column, left), (_, right)) in left.columns().into_iter().zip(right.columns()) {
+            if (left - right).abs() > tolerance {
+                mismatches.push(Mismatch::Value {
+                    client,
+                    column,
+                    left,
+                    right,
+                });
+            }
+        }
+        if left.locked != right.locked {
+            mismatches.push(Mismatch::Locked {
+                client,
+                left: left.locked,
+                right: right.locked,
+            });
+        }
+    }
+    mismatches.sort_by_key(|mismatch| match mismatch {
+        Mismatch::MissingClient { client, .. }
+        | Mismatch::DuplicateClient { client, .. }
+        | Mismatch::Value { client, .. }
+        | Mismatch::Locked { client, .. } => *client,
+    });
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{balances, reconcile, Balance, Mismatch, Side};
+    use crate::{client::Client, transaction::transactions, trial_balance::TrialBalance};
+
+    #[test]
+    fn test_reconcile() {
+        let left = "client,available,held,total,locked
+            1,10,0,10,false
+            2,5.5,1,6.5,false
+            3,0,0,0,true
+            4,1,0,1,false";
+        let right = "client, available, held, pending, total, locked
+            2, 5.50005, 1, 0, 6.50005, false
+            1, 10.01, 0, 0, 10.01, false
+            3, 0, 0, 0, 0, false
+            5, 0, 0, 0, 0, false
+            5, 0, 0, 0, 0, false";
+        let left = balances(left.as_bytes()).unwrap();
+        let right = balances(right.as_bytes()).unwrap();
+        assert_eq!(reconcile(&left, &left, Decimal::ZERO), []);
+
+        let client = Client::new;
+        assert_eq!(
+            reconcile(&left, &right, Decimal::new(1, 4)),
+            [
+                Mismatch::Value {
+                    client: client(1),
+                    column: "available",
+                    left: Decimal::new(10, 0),
+                    right: Decimal::new(1001, 2),
+                },
+                Mismatch::Value {
+                    client: client(1),
+                    column: "total",
+                    left: Decimal::new(10, 0),
+                    right: Decimal::new(1001, 2),
+                },
+                Mismatch::Locked {
+                    client: client(3),
+                    left: true,
+                    right: false,
+                },
+                Mismatch::MissingClient {
+                    client: client(4),
+                    side: Side::Right,
+                },
+                Mismatch::DuplicateClient {
+                    client: client(5),
+                    side: Side::Right,
+                },
+                Mismatch::MissingClient {
+                    client: client(5),
+                    side: Side::Left,
+                },
+            ]
+        );
+        assert!(balances("client,available\n1,2".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_reconcile_engine_output() {
+        let data = "type, client, tx, amount
+            deposit, 1, 1, 1.23456
+            deposit, 2, 2, 2
+            dispute, 2, 2,";
+        let mut trial_balance = TrialBalance::new();
+        for tx in transactions(data.as_bytes()) {
+            trial_balance.handle_transaction(tx.unwrap()).unwrap();
+        }
+        let mut output = Vec::new();
+        trial_balance.to_csv(&mut output).unwrap();
+
+        let engine: Vec<Balance> = trial_balance.snapshot().iter().map(Balance::of).collect();
+        let written = balances(output.as_slice()).unwrap();
+        assert_eq!(reconcile(&written, &engine, Decimal::ZERO), []);
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
+
+use rust_decimal::Decimal;
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+use crate::{
+    client::Client,
+    error::RulesError,
+    transaction::{Mutation, Timestamp, Transaction, Transfer},
+};
+
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+
+/// Represents what happens to a transaction when a rule fires, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// The transaction is applied and reported.
+    Flag,
+    /// The transaction is set aside for review instead of being applied.
+    /// A held deposit is kept as pending funds until it is released or rejected.
+    Hold,
+    /// The transaction is not applied.
+    Reject,
+}
+
+/// Represents the pattern a rule looks for, judged against the earlier transactions of the same client.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "when", rename_all = "snake_case")]
+pub enum Condition {
+    /// A withdrawal of more than `factor` times the average deposit of the client.
+    WithdrawalOverAverageDeposit { factor: Decimal },
+    /// More than `max` disputes filed by the client within `window_hours`.
+    /// Only disputes that carry a timestamp are counted.
+    DisputesInWindow { max: usize, window_hours: u64 },
+    /// A withdrawal of exactly the amount of the deposit the client made right before.
+    DepositThenFullWithdrawal,
+    /// A deposit of more than `amount`.
+    DepositOver { amount: Decimal },
+}
+
+/// Represents a named condition and the action taken when it holds.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub action: Action,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// Represents the rules that transactions are screened with, as loaded from a TOML file:
+///
+/// ```toml
+/// [[rules]]
+/// name = "large-withdrawal"
+/// when = "withdrawal_over_average_deposit"
+/// factor = 10
+/// action = "reject"
+///
+/// [[rules]]
+/// name = "dispute-burst"
+/// when = "disputes_in_window"
+/// max = 3
+/// window_hours = 24
+/// action = "hold"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct RuleSet {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RulesError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, RulesError> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+/// Represents a rule that fired on a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub rule: String,
+    pub action: Action,
+}
+
+/// Represents a transaction on which at least one rule fired.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreeningLine {
+    tx: Transaction,
+    hits: Vec<Hit>,
+}
+
+impl ScreeningLine {
+    pub fn tx(&self) -> &Transaction {
+        &self.tx
+    }
+
+    pub fn hits(&self) -> &[Hit] {
+        &self.hits
+    }
+
+    /// Returns the most severe of the rules that fired, the first one listed when several are equally severe.
+    pub fn decisive(&self) -> &Hit {
+        self.hits
+            .iter()
+            .rev()
+            .max_by_key(|hit| hit.action)
+            .expect("A screening line has at least one hit")
+    }
+}
+
+impl Serialize for ScreeningLine {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let amount = match &self.tx {
+            Transaction::Transfer(transfer) => Some(transfer.amount()),
+            Transaction::Mutation(_) => None,
+        };
+        let rules: Vec<&str> = self.hits.iter().map(|hit| hit.rule.as_str()).collect();
+        let mut s = serializer.serialize_struct("ScreeningLine", 6)?;
+        s.serialize_field("type", &self.tx.transaction_type())?;
+        s.serialize_field("client", &self.tx.client())?;
+        s.serialize_field("tx", &self.tx.transaction_id())?;
+        s.serialize_field("amount", &amount)?;
+        s.serialize_field("action", &self.decisive().action)?;
+        s.serialize_field("rules", &rules.join(" "))?;
+        s.end()
+    }
+}
+
+/// Represents what the screener remembers of the transactions of a client that were let through.
+#[derive(Debug, Default)]
+struct Activity {
+    deposits: u64,
+    deposited: Decimal,
+    // Amount of the previous transaction of the client if it was a deposit
+    last_deposit: Option<Decimal>,
+    // Moments of the disputes within the longest dispute window
+    disputes: VecDeque<Timestamp>,
+}
+
+/// Screens transactions with a [`RuleSet`] before they are applied and keeps a report of every rule that fired.
+///
+/// Only transactions that are let through, possibly flagged, and then applied count towards the history
+/// the rules look at; [`Screener::commit`] adds them once they are applied.
+#[derive(Debug, Default)]
+pub struct Screener {
+    rules: RuleSet,
+    // Longest window of the dispute rules in seconds, disputes are not tracked without one
+    dispute_window: Option<u64>,
+    clients: HashMap<Client, Activity>,
+    report: Vec<ScreeningLine>,
+}
+
+impl Screener {
+    pub fn new(rules: RuleSet) -> Self {
+        let dispute_window = rules
+            .rules
+            .iter()
+            .filter_map(|rule| match rule.condition {
+                Condition::DisputesInWindow { window_hours, .. } => {
+                    Some(window_hours.saturating_mul(SECONDS_PER_HOUR))
+                }
+                _ => None,
+            })
+            .max();
+        Self {
+            rules,
+            dispute_window,
+            clients: HashMap::new(),
+            report: Vec::new(),
+        }
+    }
+
+    /// Returns every transaction a rule fired on, in the order they were screened.
+    pub fn report(&self) -> &[ScreeningLine] {
+        &self.report
+    }
+
+    /// Checks the transaction against every rule and returns the most severe rule that fired.
+    pub fn screen(&mut self, tx: &Transaction) -> Option<Hit> {
+        let activity = self.clients.entry(tx.client()).or_default();
+        if let (Some(window), Some(now)) = (self.dispute_window, tx.timestamp()) {
+            while activity
+                .disputes
+                .front()
+                .is_some_and(|at| now.seconds_since(*at) > window)
+            {
+                activity.disputes.pop_front();
+            }
+        }
+
+        let hits: Vec<Hit> = self
+            .rules
+            .rules
+            .iter()
+            .filter(|rule| fires(&rule.condition, tx, activity))
+            .map(|rule| Hit {
+                rule: rule.name.clone(),
+                action: rule.action,
+            })
+            .collect();
+        if hits.is_empty() {
+            return None;
+        }
+
+        let line = ScreeningLine {
+            tx: tx.clone(),
+            hits,
+        };
+        let decisive = line.decisive().clone();
+        tracing::debug!("Rule {} fired on {:?}", decisive.rule, tx);
+        self.report.push(line);
+        Some(decisive)
+    }
+
+    /// Adds a transaction that was let through and applied to the history of its client.
+    pub fn commit(&mut self, tx: &Transaction) {
+        let activity = self.clients.entry(tx.client()).or_default();
+        record(tx, activity, self.dispute_window.is_some());
+    }
+}
+
+/// Writes the screening lines as csv.
+pub fn to_csv<'a, I, W>(lines: I, w: &mut W) -> Result<(), csv::Error>
+where
+    I: IntoIterator<Item = &'a ScreeningLine>,
+    W: std::io::Write,
+{
+    let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(w);
+    lines.into_iter().try_for_each(|line| wtr.serialize(line))?;
+    wtr.flush()?;
+    Ok(())
+}
+
+fn fires(condition: &Condition, tx: &Transaction, activity: &Activity) -> bool {
+    match (condition, tx) {
+        (
+            Condition::WithdrawalOverAverageDeposit { factor },
+            Transaction::Transfer(Transfer::Withdrawal(withdrawal)),
+        ) => {
+            activity.deposits > 0
+                && withdrawal.amount() * Decimal::from(activity.deposits)
+                    > factor * activity.deposited
+        }
+        (
+            Condition::DisputesInWindow { max, window_hours },
+            Transaction::Mutation(Mutation::Dispute(dispute)),
+        ) => dispute.timestamp().is_some_and(|now| {
+            let window = window_hours.saturating_mul(SECONDS_PER_HOUR);
+            let recent = activity
+                .disputes
+                .iter()
+                .filter(|at| now.seconds_since(**at) <= window)
+                .count();
+            recent + 1 > *max
+        }),
+        (
+            Condition::DepositThenFullWithdrawal,
+            Transaction::Transfer(Transfer::Withdrawal(withdrawal)),
+        ) => activity.last_deposit == Some(withdrawal.amount()),
+        (Condition::DepositOver { amount }, Transaction::Transfer(Transfer::Deposit(deposit))) => {
+            deposit.amount() > *amount
+        }
+        _ => false,
+    }
+}
+
+/// Adds a transaction that was applied to the activity of its client.
+fn record(tx: &Transaction, activity: &mut Activity, track_disputes: bool) {
+    activity.last_deposit = None;
+    match tx {
+        Transaction::Transfer(Transfer::Deposit(deposit)) => {
+            activity.deposits += 1;
+            activity.deposited += deposit.amount();
+            activity.last_deposit = Some(deposit.amount());
+        }
+        Transaction::Mutation(Mutation::Dispute(dispute)) if track_disputes => {
+            if let Some(at) = dispute.timestamp() {
+                activity.disputes.push_back(at);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::{to_csv, Action, Condition, Hit, RuleSet, Screener};
+    use crate::transaction::{transactions, Transaction};
+
+    const RULES: &str = r#"
+        [[rules]]
+        name = "large-withdrawal"
+        when = "withdrawal_over_average_deposit"
+        factor = 10
+        action = "reject"
+
+        [[rules]]
+        name = "dispute-burst"
+        when = "disputes_in_window"
+        max = 2
+        window_hours = 24
+        action = "hold"
+
+        [[rules]]
+        name = "deposit-drained"
+        when = "deposit_then_full_withdrawal"
+        action = "flag"
+    "#;
+
+    #[test]
+    fn test_load_rules() {
+        let rules = RuleSet::from_toml(RULES).unwrap();
+        assert_eq!(rules.rules.len(), 3);
+        assert_eq!(
+            rules.rules[0].condition,
+            Condition::WithdrawalOverAverageDeposit {
+                factor: Decimal::new(10, 0)
+            }
+        );
+        assert_eq!(rules.rules[1].action, Action::Hold);
+        assert!(
+            RuleSet::from_toml("[[rules]]\nname = \"x\"\nwhen = \"never\"\naction = \"flag\"")
+                .is_err()
+        );
+        assert_eq!(RuleSet::from_toml("").unwrap(), RuleSet::default());
+    }
+
+    #[test]
+    fn test_screen() {
+        let data = "type, client, tx, amount, timestamp
+            deposit, 1, 1, 10, 0
+            deposit, 1, 2, 30, 0
+            withdrawal, 1, 3, 30, 10
+            withdrawal, 1, 4, 201, 10
+            withdrawal, 1, 5, 199, 10
+            dispute, 1, 1, , 3600
+            dispute, 1, 2, , 7200
+            dispute, 1, 3, , 7300
+            dispute, 1, 4, , 100000
+            deposit, 2, 6, 5, 0
+            withdrawal, 2, 7, 5, 1";
+        let hit = |rule: &str, action| {
+            Some(Hit {
+                rule: rule.to_string(),
+                action,
+            })
+        };
+        let expected = [
+            None,
+            None,
+            hit("deposit-drained", Action::Flag),
+            // The average deposit is 20
+            hit("large-withdrawal", Action::Reject),
+            None,
+            None,
+            None,
+            hit("dispute-burst", Action::Hold),
+            // The earlier disputes are more than a day ago and the held one does not count
+            None,
+            None,
+            hit("deposit-drained", Action::Flag),
+        ];
+        let mut screener = Screener::new(RuleSet::from_toml(RULES).unwrap());
+        let transactions: Vec<Transaction> =
+            transactions(data.as_bytes()).map(Result::unwrap).collect();
+        for (index, (tx, expected)) in transactions.iter().zip(expected).enumerate() {
+            let hit = screener.screen(tx);
+            assert_eq!(hit, expected, "Failed on index {}", index);
+            if hit.is_none_or(|hit| hit.action == Action::Flag) {
+                screener.commit(tx);
+            }
+        }
+
+        assert_eq!(screener.report().len(), 4);
+        let mut report = Vec::new();
+        to_csv(screener.report(), &mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "type,client,tx,amount,action,rules
+withdrawal,1,3,30,flag,deposit-drained
+withdrawal,1,4,201,reject,large-withdrawal
+dispute,1,3,,hold,dispute-burst
+withdrawal,2,7,5,flag,deposit-drained
+"
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    client::Client,
+    metrics::{self, Histogram},
+    transaction::{transactions, Transaction, TransactionId, TransactionRow},
+    trial_balance::TrialBalance,
+};
+
+// Largest request body that is accepted.
+const MAX_BODY: usize = 16 * 1024 * 1024;
+// Idle connections are closed after this time.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+// Header that is assumed for CSV bodies that do not start with one.
+const CSV_HEADER: &str = "type,client,tx,amount,timestamp\n";
+
+/// Represents a long running service that handles transactions submitted over HTTP.
+///
+/// Endpoints:
+/// - `POST /transactions` handles CSV rows, or a JSON object or array when sent as `application/json`,
+///   and returns the result of every transaction
+/// - `GET /accounts` lists all accounts
+/// - `GET /accounts/<client>` returns a single account
+/// - `GET /snapshot` returns the accounts in the CSV output format
+/// - `GET /metrics` returns the metrics in the Prometheus text format
+pub struct Server {
+    listener: TcpListener,
+    trial_balance: Arc<Mutex<TrialBalance>>,
+    // Time taken to handle each submitted transaction.
+    latency: Arc<Mutex<Histogram>>,
+    // File the metrics are written to periodically, with the interval.
+    metrics_file: Option<(PathBuf, Duration)>,
+}
+
+/// Represents the result of a single submitted transaction.
+#[derive(Debug, Serialize)]
+struct Outcome {
+    status: Status,
+    client: Option<Client>,
+    tx: Option<TransactionId>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Accepted,
+    Rejected,
+    Invalid,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    json: bool,
+    close: bool,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json<T: Serialize>(value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(body) => Self {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(err) => Self::error(500, &err.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: format!("{message}\n").into_bytes(),
+        }
+    }
+}
+
+impl Server {
+    /// Binds the service to the address, handling transactions on the trial balance.
+    /// Statistics are enabled on the trial balance when they are not yet, as the metrics are based on them.
+    pub fn bind<A: ToSocketAddrs>(addr: A, trial_balance: TrialBalance) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            trial_balance: Arc::new(Mutex::new(trial_balance.with_stats())),
+            latency: Arc::new(Mutex::new(Histogram::new())),
+            metrics_file: None,
+        })
+    }
+
+    /// Writes the metrics to the file every `interval` while running, for the textfile collector of the
+    /// node exporter.
+    pub fn with_metrics_file(mut self, path: PathBuf, interval: Duration) -> Self {
+        self.metrics_file = Some((path, interval));
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until the listener fails, serving each of them on its own thread.
+    pub fn run(self) -> io::Result<()> {
+        if let Some((path, interval)) = self.metrics_file {
+            let trial_balance = Arc::clone(&self.trial_balance);
+            let latency = Arc::clone(&self.latency);
+            thread::spawn(move || loop {
+                let metrics = render_metrics(&trial_balance, &latency);
+                if let Err(err) = metrics::write_textfile(&path, &metrics) {
+                    tracing::error!("Could not write metrics to {:?} {:?}", path, err);
+                }
+                thread::sleep(interval);
+            });
+        }
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let trial_balance = Arc::clone(&self.trial_balance);
+            let latency = Arc::clone(&self.latency);
+            thread::spawn(move || {
+                if let Err(err) = serve_connection(stream, &trial_balance, &latency) {
+                    tracing::debug!("Connection closed {:?}", err);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn render_metrics(trial_balance: &Mutex<TrialBalance>, latency: &Mutex<Histogram>) -> String {
+    let trial_balance = trial_balance.lock().expect("A request handler panicked");
+    let latency = latency.lock().expect("A request handler panicked");
+    metrics::render(&trial_balance, &latency)
+}
+
+/// Serves the requests of a connection until the client closes it.
+fn serve_connection(
+    stream: TcpStream,
+    trial_balance: &Mutex<TrialBalance>,
+    latency: &Mutex<Histogram>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                write_response(&mut writer, &Response::error(400, &err.to_string()), true)?;
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+        let response = respond(trial_balance, latency, &request);
+        write_response(&mut writer, &response, request.close)?;
+        if request.close {
+            return Ok(());
+        }
+    }
+}
+
+/// Reads the next request, returning `None` when the connection was closed in between requests.
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Option<Request>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("Malformed request line"));
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        json: false,
+        close: version == "HTTP/1.0",
+        body: Vec::new(),
+    };
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("Unexpected end of headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(invalid("Malformed header"));
+        };
+        let value = value.trim();
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => {
+                content_length = value
+                    .parse()
+                    .map_err(|_| invalid("Invalid Content-Length"))?;
+            }
+            "content-type" => request.json = value.starts_with("application/json"),
+            "connection" => request.close = value.eq_ignore_ascii_case("close"),
+            _ => {}
+        }
+    }
+
+    if content_length > MAX_BODY {
+        return Err(invalid("Request body too large"));
+    }
+    request.body = vec![0; content_length];
+    reader.read_exact(&mut request.body)?;
+    Ok(Some(request))
+}
+
+fn write_response<W: Write>(writer: &mut W, response: &Response, close: bool) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len(),
+        if close { "close" } else { "keep-alive" }
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
+
+fn respond(
+    trial_balance: &Mutex<TrialBalance>,
+    latency: &Mutex<Histogram>,
+    request: &Request,
+) -> Response {
+    let path = request.path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if let ("GET", ["metrics"]) = (request.method.as_str(), segments.as_slice()) {
+        return Response {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body: render_metrics(trial_balance, latency).into_bytes(),
+        };
+    }
+    let mut trial_balance = trial_balance.lock().expect("A request handler panicked");
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["transactions"]) => {
+            let transactions = if request.json {
+                match json_transactions(&request.body) {
+                    Ok(transactions) => transactions,
+                    Err(err) => return Response::error(400, &err.to_string()),
+                }
+            } else {
+                csv_transactions(&request.body)
+            };
+            let mut latency = latency.lock().expect("A request handler panicked");
+            let outcomes: Vec<Outcome> = transactions
+                .into_iter()
+                .map(|tx| handle(&mut trial_balance, &mut latency, tx))
+                .collect();
+            Response::json(&outcomes)
+        }
+        ("GET", ["accounts"]) => Response::json(&trial_balance.snapshot()),
+        ("GET", ["accounts", client]) => {
+            let Ok(client) = client.parse().map(Client::new) else {
+                return Response::error(400, "Invalid client id");
+            };
+            match trial_balance.account(client) {
+                Some(account) => Response::json(account),
+                None => Response::error(404, "Unknown client"),
+            }
+        }
+        ("GET", ["snapshot"]) => {
+            let mut body = Vec::new();
+            match trial_balance.to_csv(&mut body) {
+                Ok(()) => Response {
+                    status: 200,
+                    content_type: "text/csv",
+                    body,
+                },
+                Err(err) => Response::error(500, &err.to_string()),
+            }
+        }
+        (_, ["transactions"] | ["accounts"] | ["accounts", _] | ["snapshot"] | ["metrics"]) => {
+            Response::error(405, "Method not allowed")
+        }
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+/// Handles a single submitted transaction.
+fn handle(
+    trial_balance: &mut TrialBalance,
+    latency: &mut Histogram,
+    tx: Result<Transaction, String>,
+) -> Outcome {
+    let tx = match tx {
+        Ok(tx) => tx,
+        Err(err) => {
+            trial_balance.record_invalid();
+            return Outcome {
+                status: Status::Invalid,
+                client: None,
+                tx: None,
+                error: Some(err),
+            };
+        }
+    };
+    let (client, transaction_id) = (tx.client(), tx.transaction_id());
+    let started = Instant::now();
+    let res = trial_balance.handle_transaction(tx);
+    latency.observe(started.elapsed());
+    let (status, error) = match res {
+        Ok(()) => (Status::Accepted, None),
+        Err(err) => (Status::Rejected, Some(err.to_string())),
+    };
+    Outcome {
+        status,
+        client: Some(client),
+        tx: Some(transaction_id),
+        error,
+    }
+}
+
+/// Reads the transactions of a CSV body, which does not need to start with a header.
+fn csv_transactions(body: &[u8]) -> Vec<Result<Transaction, String>> {
+    let header: &[u8] = if body.trim_ascii_start().starts_with(b"type") {
+        b""
+    } else {
+        CSV_HEADER.as_bytes()
+    };
+    transactions(header.chain(body))
+        .map(|tx| tx.map_err(|err| err.to_string()))
+        .collect()
+}
+
+/// Reads the transactions of a JSON body holding either a single transaction or an array of them.
+fn json_transactions(body: &[u8]) -> serde_json::Result<Vec<Result<Transaction, String>>> {
+    let values = match serde_json::from_slice(body)? {
+        serde_json::Value::Array(values) => values,
+        value => vec![value],
+    };
+    Ok(values
+        .into_iter()
+        .map(|value| {
+            let tx_row: TransactionRow =
+                serde_json::from_value(value).map_err(|err| err.to_string())?;
+            Transaction::try_from(tx_row).map_err(|err| err.to_string())
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{SocketAddr, TcpStream},
+        thread,
+    };
+
+    use super::Server;
+    use crate::trial_balance::TrialBalance;
+
+    fn start() -> SocketAddr {
+        let server = Server::bind("127.0.0.1:0", TrialBalance::new()).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        addr
+    }
+
+    /// Sends a request over a fresh connection and returns the status and body of the response.
+    fn request(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        json: bool,
+        body: &str,
+    ) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let content_type = if json { "application/json" } else { "text/csv" };
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let status = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let mut content_length = 0;
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if line.trim_end().is_empty() {
+                break;
+            }
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        (status, String::from_utf8(body).unwrap())
+    }
+
+    #[test]
+    fn test_serve() {
+        let addr = start();
+
+        let (status, body) = request(
+            addr,
+            "POST",
+            "/transactions",
+            false,
+            "deposit, 1, 1, 10.0\nwithdrawal, 1, 2, 20.0\ntransfer, 1, 3, 1.0\n",
+        );
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            r#"[{"status":"accepted","client":1,"tx":1,"error":null},{"status":"rejected","client":1,"tx":2,"error":"Error: Insufficient funds"},{"status":"invalid","client":null,"tx":null,"error":"Could not read row: CSV deserialize error: record 3 (line: 4, byte: 75): unknown variant `transfer`, expected one of `deposit`, `withdrawal`, `dispute`, `resolve`, `chargeback`, `finalize`, `release`, `reject`"}]"#
+        );
+
+        let (status, body) = request(
+            addr,
+            "POST",
+            "/transactions",
+            true,
+            r#"[{"type": "deposit", "client": 2, "tx": 4, "amount": "2.5"}, {"type": "dispute", "client": 1, "tx": 1}]"#,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            r#"[{"status":"accepted","client":2,"tx":4,"error":null},{"status":"accepted","client":1,"tx":1,"error":null}]"#
+        );
+        let (status, _) = request(addr, "POST", "/transactions", true, "{");
+        assert_eq!(status, 400);
+
+        assert_eq!(
+            request(addr, "GET", "/accounts/1", false, ""),
+            (
+                200,
+                r#"{"client":1,"available":"0","held":"10","pending":"0","total":"10","locked":false}"#
+                    .to_string()
+            )
+        );
+        assert_eq!(request(addr, "GET", "/accounts/3", false, "").0, 404);
+        assert_eq!(request(addr, "GET", "/accounts/x", false, "").0, 400);
+        let (status, body) = request(addr, "GET", "/accounts", false, "");
+        assert_eq!(status, 200);
+        assert!(body.starts_with(r#"[{"client":1,"#));
+        let (status, body) = request(addr, "GET", "/snapshot", false, "");
+        assert_eq!(status, 200);
+        // The accounts are written in no particular order
+        let mut lines: Vec<&str> = body.lines().collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                "1,0,10,0,10,false",
+                "2,2.5,0,0,2.5,false",
+                "client,available,held,pending,total,locked"
+            ]
+        );
+        let (status, body) = request(addr, "GET", "/metrics", false, "");
+        assert_eq!(status, 200);
+        for line in [
+            "csv_reader_transactions_total{type=\"deposit\",outcome=\"applied\"} 2",
+            "csv_reader_transaction_errors_total{type=\"withdrawal\",error=\"insufficient_funds\"} 1",
+            "csv_reader_invalid_rows_total 1",
+            "csv_reader_accounts 2",
+            "csv_reader_held_funds 10",
+            "csv_reader_transaction_duration_seconds_count 4",
+        ] {
+            assert!(body.lines().any(|l| l == line), "{line} in {body}");
+        }
+        assert_eq!(request(addr, "DELETE", "/snapshot", false, "").0, 405);
+        assert_eq!(request(addr, "GET", "/unknown", false, "").0, 404);
+    }
+
+    #[test]
+    fn test_serve_keep_alive() {
+        let addr = start();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        for tx in 1..=2 {
+            let body = format!("type, client, tx, amount\ndeposit, 1, {tx}, 1.0");
+            write!(
+                stream,
+                "POST /transactions HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+        drop(stream.shutdown(std::net::Shutdown::Write));
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).unwrap();
+        assert_eq!(responses.matches("HTTP/1.1 200 OK").count(), 2);
+        assert_eq!(
+            request(addr, "GET", "/accounts/1", false, "").1,
+            r#"{"client":1,"available":"2","held":"0","pending":"0","total":"2","locked":false}"#
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{
+        mpsc::{sync_channel, SyncSender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    account::Account,
+    client::Client,
+    error::TransactionError,
+    transaction::{Mutation, Timestamp, Transaction, TransactionId, Transfer},
+    trial_balance::TrialBalance,
+};
+
+// Number of jobs that are sent to a shard at once, this amortizes the cost of the channel.
+const BATCH_SIZE: usize = 256;
+// Number of batches that can be queued for a shard before the router blocks.
+const QUEUE_SIZE: usize = 16;
+
+/// Represents work for a single shard.
+enum Job {
+    Handle(Transaction),
+    /// A transaction that was rejected by the router.
+    Reject(Transaction, TransactionError),
+    ExpireDisputes(Timestamp),
+    /// Signals that every earlier job of the shard has been handled.
+    Sync(SyncSender<()>),
+}
+
+/// Represents a trial balance that is partitioned by client over multiple worker threads.
+///
+/// Each shard owns the accounts and ledger of its clients and handles their transactions in input order.
+/// Transactions of different clients only meet when a client mutates the transaction of another,
+/// so the resulting accounts are identical to those of a single [`TrialBalance`] for inputs with
+/// non-decreasing timestamps.
+///
+/// The router keeps the owner of every transaction id so that ids stay unique across shards.
+/// A mutation of a transaction of a client on another shard waits for both shards to catch up
+/// and is then handled on the two of them together, exactly like a single trial balance would.
+/// When the shards screen transactions with rules, a transfer that a rule holds or rejects does not
+/// take its id, so a transfer that reuses the id of a client on another shard is handled the same way.
+pub struct ShardedTrialBalance {
+    senders: Vec<SyncSender<Vec<Job>>>,
+    batches: Vec<Vec<Job>>,
+    shards: Vec<Arc<Mutex<TrialBalance>>>,
+    workers: Vec<JoinHandle<()>>,
+    owners: HashMap<TransactionId, Client>,
+    // Whether the shards screen transactions, so the first transfer of an id may not have taken it
+    screened: bool,
+    latest: Option<Timestamp>,
+}
+
+impl ShardedTrialBalance {
+    /// Spawns `shards` worker threads, each with a trial balance created by `new_shard`.
+    pub fn new<F>(shards: usize, new_shard: F) -> Self
+    where
+        F: Fn() -> TrialBalance,
+    {
+        let shards = shards.max(1);
+        let mut senders = Vec::with_capacity(shards);
+        let mut workers = Vec::with_capacity(shards);
+        let shards: Vec<_> = (0..shards)
+            .map(|_| Arc::new(Mutex::new(new_shard())))
+            .collect();
+        let screened = shards.iter().any(|shard| lock(shard).screener().is_some());
+        for (index, shard) in shards.iter().enumerate() {
+            let (sender, receiver) = sync_channel::<Vec<Job>>(QUEUE_SIZE);
+            let shard = Arc::clone(shard);
+            let worker = thread::Builder::new()
+                .name(format!("shard-{index}"))
+                .spawn(move || {
+                    for batch in receiver {
+                        let mut trial_balance = lock(&shard);
+                        for job in batch {
+                            match job {
+                                Job::Handle(tx) => {
+                                    if let Err(err) = trial_balance.handle_transaction(tx) {
+                                        tracing::error!("Could not handle transaction {:?}", err);
+                                    }
+                                }
+                                Job::Reject(tx, err) => {
+                                    tracing::error!("Could not handle transaction {:?}", err);
+                                    trial_balance.reject(tx, err);
+                                }
+                                Job::ExpireDisputes(now) => trial_balance.expire_disputes(now),
+                                Job::Sync(done) => {
+                                    let _ = done.send(());
+                                }
+                            }
+                        }
+                    }
+                })
+                .expect("Could not spawn shard");
+            senders.push(sender);
+            workers.push(worker);
+        }
+
+        Self {
+            senders,
+            batches: (0..shards.len())
+                .map(|_| Vec::with_capacity(BATCH_SIZE))
+                .collect(),
+            shards,
+            workers,
+            owners: HashMap::with_capacity(100000),
+            screened,
+            latest: None,
+        }
+    }
+
+    /// Routes the transaction to the shard of its client.
+    ///
+    /// Ids taken on another shard are rejected as duplicates here, as a shard only knows the transactions
+    /// of its own clients.
+    pub fn handle_transaction(&mut self, tx: Transaction) {
+        self.latest = self.latest.max(tx.timestamp());
+        let shard = self.shard(tx.client());
+
+        let job = match tx {
+            Transaction::Transfer(transfer) => match self.owners.get(&transfer.transaction_id()) {
+                None => {
+                    self.owners
+                        .insert(transfer.transaction_id(), transfer.client());
+                    Job::Handle(Transaction::Transfer(transfer))
+                }
+                // The shard knows the transactions of its own clients
+                Some(&owner) if self.shard(owner) == shard => {
+                    Job::Handle(Transaction::Transfer(transfer))
+                }
+                Some(&owner) if self.screened => {
+                    return self.handle_foreign_transfer(shard, self.shard(owner), transfer);
+                }
+                Some(_) => {
+                    let err = TransactionError::DuplicateTransaction(transfer.transaction_id());
+                    Job::Reject(Transaction::Transfer(transfer), err)
+                }
+            },
+            Transaction::Mutation(mutation) => match self.owners.get(&mutation.transaction_id()) {
+                Some(&owner) if self.shard(owner) != shard => {
+                    return self.handle_foreign_mutation(shard, self.shard(owner), mutation);
+                }
+                _ => Job::Handle(Transaction::Mutation(mutation)),
+            },
+        };
+        self.push(shard, job);
+    }
+
+    /// Handles a transfer on the shard of its client whose id was first used on the `owner` shard,
+    /// the id moves to the client when the owner did not take it.
+    fn handle_foreign_transfer(&mut self, shard: usize, owner: usize, transfer: Transfer) {
+        let (id, client) = (transfer.transaction_id(), transfer.client());
+        self.sync(shard);
+        self.sync(owner);
+        let taken = {
+            let mut trial_balance = lock(&self.shards[shard]);
+            let mut owner = lock(&self.shards[owner]);
+            if let Err(err) = trial_balance.handle_foreign_transfer(&mut owner, transfer) {
+                tracing::error!("Could not handle transaction {:?}", err);
+            }
+            trial_balance.contains_transaction(id)
+        };
+        if taken {
+            self.owners.insert(id, client);
+        }
+    }
+
+    /// Handles a mutation on the shard of its client of a transaction that is kept by the `owner` shard.
+    fn handle_foreign_mutation(&mut self, shard: usize, owner: usize, mutation: Mutation) {
+        self.sync(shard);
+        self.sync(owner);
+        let mut trial_balance = lock(&self.shards[shard]);
+        let mut owner = lock(&self.shards[owner]);
+        if let Err(err) = trial_balance.handle_foreign_mutation(&mut owner, mutation) {
+            tracing::error!("Could not handle transaction {:?}", err);
+        }
+    }
+
+    /// Waits until the shard handled every job that was routed to it.
+    fn sync(&mut self, shard: usize) {
+        let (done, synced) = sync_channel(1);
+        self.push(shard, Job::Sync(done));
+        self.flush(shard);
+        synced.recv().expect("Shard stopped unexpectedly");
+    }
+
+    /// Handles all queued transactions and returns the trial balance of every shard.
+    pub fn finish(mut self) -> Vec<TrialBalance> {
+        // Disputes that passed their deadline at the latest moment seen by any shard
+        if let Some(latest) = self.latest {
+            for shard in 0..self.senders.len() {
+                self.push(shard, Job::ExpireDisputes(latest));
+            }
+        }
+        for shard in 0..self.senders.len() {
+            self.flush(shard);
+        }
+        drop(mem::take(&mut self.senders));
+        for worker in self.workers {
+            worker.join().expect("Shard panicked");
+        }
+        self.shards
+            .into_iter()
+            .map(|shard| {
+                Arc::into_inner(shard)
+                    .expect("Shard is still shared")
+                    .into_inner()
+                    .expect("Shard panicked")
+            })
+            .collect()
+    }
+
+    fn shard(&self, client: Client) -> usize {
+        client.id() as usize % self.senders.len()
+    }
+
+    fn push(&mut self, shard: usize, job: Job) {
+        self.batches[shard].push(job);
+        if self.batches[shard].len() >= BATCH_SIZE {
+            self.flush(shard);
+        }
+    }
+
+    fn flush(&mut self, shard: usize) {
+        let batch = mem::replace(&mut self.batches[shard], Vec::with_capacity(BATCH_SIZE));
+        if !batch.is_empty() {
+            self.senders[shard]
+                .send(batch)
+                .expect("Shard stopped unexpectedly");
+        }
+    }
+}
+
+fn lock(shard: &Mutex<TrialBalance>) -> MutexGuard<'_, TrialBalance> {
+    shard.lock().expect("Shard panicked")
+}
+
+/// Returns a copy of the accounts of all shards ordered by client.
+pub fn snapshot(shards: &[TrialBalance]) -> Vec<Account> {
+    let mut accounts: Vec<Account> = shards.iter().flat_map(|shard| shard.snapshot()).collect();
+    accounts.sort_by_key(|account| account.client());
+    accounts
+}
+
+/// Writes the accounts of all shards as csv.
+pub fn to_csv<W>(shards: &[TrialBalance], w: &mut W) -> Result<(), csv::Error>
+where
+    W: std::io::Write,
+{
+    let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(w);
+    snapshot(shards)
+        .iter()
+        .try_for_each(|account| wtr.serialize(account))?;
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedTrialBalance;
+    use crate::{
+        policy::DisputePolicy,
+        rules::{RuleSet, Screener},
+        stats::Stats,
+        transaction::{transactions, Transaction},
+        trial_balance::TrialBalance,
+    };
+
+    fn input() -> String {
+        let mut data = "type,client,tx,amount,timestamp\n".to_string();
+        for i in 0..2000u32 {
+            let client = i % 37;
+            let row = match i % 7 {
+                0..=2 => format!("deposit,{client},{i},{}.5,{i}", i % 100),
+                3 => format!("withdrawal,{client},{i},{}.25,{i}", i % 50),
+                4 => format!("dispute,{client},{},,{i}", i.saturating_sub(37)),
+                // Settles the dispute of the previous row or leaves it open to be auto resolved
+                5 if i % 30 == 5 => {
+                    format!("chargeback,{},{},,{i}", (i - 1) % 37, i.saturating_sub(38))
+                }
+                5 if i % 2 == 0 => {
+                    format!("resolve,{},{},,{i}", (i - 1) % 37, i.saturating_sub(38))
+                }
+                5 => format!("withdrawal,{client},{i},1.0,{i}"),
+                // Duplicates and mutations of transactions of other clients
+                6 if i % 2 == 0 => format!("deposit,{client},{},1.0,{i}", i - 6),
+                _ => format!("dispute,{client},{},,{i}", i - 6),
+            };
+            data.push_str(&row);
+            data.push('\n');
+        }
+        data
+    }
+
+    #[test]
+    fn test_sharded_matches_sequential() {
+        let data = input();
+        let policy = DisputePolicy::new().with_auto_resolve_after(500);
+        let parsed: Vec<Transaction> = transactions(data.as_bytes())
+            .map(|tx| tx.unwrap())
+            .collect();
+
+        let mut sequential = TrialBalance::new().with_dispute_policy(policy);
+        for tx in parsed.iter().cloned() {
+            let _ = sequential.handle_transaction(tx);
+        }
+        let accounts = sequential.snapshot();
+        assert!(accounts.iter().any(|account| account.locked()));
+        assert!(accounts.iter().any(|account| !account.held().is_zero()));
+        assert!(!sequential.auto_resolved().is_empty());
+
+        for shards in [1, 3, 8] {
+            let mut sharded = ShardedTrialBalance::new(shards, || {
+                TrialBalance::new().with_dispute_policy(policy)
+            });
+            for tx in parsed.iter().cloned() {
+                sharded.handle_transaction(tx);
+            }
+            let shards = sharded.finish();
+            assert_eq!(super::snapshot(&shards), sequential.snapshot());
+        }
+    }
+
+    #[test]
+    fn test_sharded_screened_matches_sequential() {
+        let rules = RuleSet::from_toml(
+            r#"
+            [[rules]]
+            name = "large-deposit"
+            when = "deposit_over"
+            amount = 90
+            action = "reject"
+            "#,
+        )
+        .unwrap();
+        let new_trial_balance = || {
+            TrialBalance::new()
+                .with_screener(Screener::new(rules.clone()))
+                .with_stats()
+        };
+        // A rejected deposit does not take its id, so it can be used again by any client
+        let mut data = input();
+        data.push_str(
+            "deposit,1,5000,500,2000
+deposit,1,5000,5,2001
+",
+        );
+        data.push_str(
+            "deposit,2,5001,500,2002
+deposit,3,5001,5,2003
+deposit,2,5001,5,2004
+",
+        );
+        let parsed: Vec<Transaction> = transactions(data.as_bytes())
+            .map(|tx| tx.unwrap())
+            .collect();
+
+        let mut sequential = new_trial_balance();
+        for tx in parsed.iter().cloned() {
+            let _ = sequential.handle_transaction(tx);
+        }
+        let stats = sequential.stats().unwrap();
+        assert!(stats.types["deposit"].errors["rejected_by_rule"] > 0);
+
+        for shards in [1, 2, 3, 8] {
+            let mut sharded = ShardedTrialBalance::new(shards, new_trial_balance);
+            for tx in parsed.iter().cloned() {
+                sharded.handle_transaction(tx);
+            }
+            let shards = sharded.finish();
+            assert_eq!(super::snapshot(&shards), sequential.snapshot());
+            let mut merged = Stats::new();
+            for shard in &shards {
+                merged.merge(&shard.stats().unwrap());
+            }
+            assert_eq!(merged.types, stats.types);
+            let screened: usize = shards
+                .iter()
+                .map(|shard| shard.screener().unwrap().report().len())
+                .sum();
+            assert_eq!(screened, sequential.screener().unwrap().report().len());
+        }
+    }
+}
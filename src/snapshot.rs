@@ -0,0 +1,174 @@
+use std::fmt;
+
+use crate::{
+    account::Account,
+    transaction::{error::DeserializationError, Timestamp, Transaction},
+    trial_balance::TrialBalance,
+};
+
+/// Represents the moment at which the accounts should be captured during a replay.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Cutoff {
+    /// The state after the given number of rows has been processed.
+    Sequence(u64),
+    /// The state after all rows up to and including the given timestamp have been processed.
+    /// Rows without a timestamp never pass a cut-off.
+    Timestamp(Timestamp),
+}
+
+impl fmt::Display for Cutoff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cutoff::Sequence(seq) => write!(f, "seq:{seq}"),
+            Cutoff::Timestamp(timestamp) => write!(f, "time:{}", timestamp.as_secs()),
+        }
+    }
+}
+
+/// Represents the accounts exactly as they stood at a [`Cutoff`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+    cutoff: Cutoff,
+    accounts: Vec<Account>,
+}
+
+impl Snapshot {
+    pub fn cutoff(&self) -> Cutoff {
+        self.cutoff
+    }
+
+    pub fn accounts(&self) -> &[Account] {
+        &self.accounts
+    }
+}
+
+/// Replays the transactions on the trial balance and captures the accounts at every cut-off.
+///
+/// All cut-offs are captured in a single pass. Cut-offs that are not reached before the input ends
+/// capture the final state. The snapshots are returned in the order of `cutoffs`.
+pub fn balances_at<I>(
+    trial_balance: &mut TrialBalance,
+    transactions: I,
+    cutoffs: &[Cutoff],
+) -> Vec<Snapshot>
+where
+    I: IntoIterator<Item = Result<Transaction, DeserializationError>>,
+{
+    let mut snapshots: Vec<Option<Snapshot>> = vec![None; cutoffs.len()];
+
+    for (processed, tx) in transactions.into_iter().enumerate() {
+        let timestamp = tx.as_ref().ok().and_then(|tx| tx.timestamp());
+        capture(
+            trial_balance,
+            cutoffs,
+            &mut snapshots,
+            |cutoff| match cutoff {
+                Cutoff::Sequence(seq) => seq <= processed as u64,
+                Cutoff::Timestamp(at) => timestamp.is_some_and(|timestamp| timestamp > at),
+            },
+        );
+
+        match tx {
+            Ok(tx) => {
+                if let Err(err) = trial_balance.handle_transaction(tx) {
+                    tracing::error!("Could not handle transaction {:?}", err);
+                }
+            }
+            Err(err) => tracing::error!("Could not parse transaction {:?}", err),
+        }
+    }
+    capture(trial_balance, cutoffs, &mut snapshots, |_| true);
+
+    snapshots.into_iter().flatten().collect()
+}
+
+/// Captures a snapshot for every cut-off that has not been captured yet and is `passed`.
+fn capture<F>(
+    trial_balance: &mut TrialBalance,
+    cutoffs: &[Cutoff],
+    snapshots: &mut [Option<Snapshot>],
+    passed: F,
+) where
+    F: Fn(Cutoff) -> bool,
+{
+    for (cutoff, snapshot) in cutoffs.iter().zip(snapshots.iter_mut()) {
+        if snapshot.is_none() && passed(*cutoff) {
+            if let Cutoff::Timestamp(at) = cutoff {
+                // Disputes that passed their deadline before the cut-off are resolved by then
+                trial_balance.expire_disputes(*at);
+            }
+            *snapshot = Some(Snapshot {
+                cutoff: *cutoff,
+                accounts: trial_balance.snapshot(),
+            });
+        }
+    }
+}
+
+/// Writes the snapshots as csv with the cut-off as leading column.
+pub fn to_csv<W>(snapshots: &[Snapshot], w: &mut W) -> Result<(), csv::Error>
+where
+    W: std::io::Write,
+{
+    let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(w);
+    wtr.write_record([
+        "cutoff",
+        "client",
+        "available",
+        "held",
+        "pending",
+        "total",
+        "locked",
+    ])?;
+    for snapshot in snapshots {
+        let cutoff = snapshot.cutoff.to_string();
+        snapshot
+            .accounts
+            .iter()
+            .try_for_each(|account| wtr.serialize((&cutoff, account)))?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{balances_at, to_csv, Cutoff};
+    use crate::{
+        transaction::{transactions, Timestamp},
+        trial_balance::TrialBalance,
+    };
+
+    const DATA: &str = "type,client,tx,amount,timestamp
+deposit,1,1,10.0,100
+deposit,2,2,5.0,200
+dispute,1,1,,300
+withdrawal,2,3,1.0,400
+resolve,1,1,,500";
+
+    #[test]
+    fn test_balances_at() {
+        let cutoffs = [
+            Cutoff::Timestamp(Timestamp::from_secs(350)),
+            Cutoff::Sequence(1),
+            Cutoff::Sequence(0),
+            Cutoff::Sequence(100),
+        ];
+        let mut trial_balance = TrialBalance::new();
+        let snapshots = balances_at(&mut trial_balance, transactions(DATA.as_bytes()), &cutoffs);
+
+        let mut output = Vec::new();
+        to_csv(&snapshots, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "cutoff,client,available,held,pending,total,locked
+time:350,1,0,10,0,10,false
+time:350,2,5,0,0,5,false
+seq:1,1,10,0,0,10,false
+seq:100,1,10,0,0,10,false
+seq:100,2,4,0,0,4,false
+"
+        );
+        assert_eq!(snapshots[3].accounts(), trial_balance.snapshot());
+    }
+}
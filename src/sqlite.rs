@@ -0,0 +1,374 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use rusqlite::{params, types::Type, Connection, OptionalExtension, Row};
+use rust_decimal::Decimal;
+
+use crate::{
+    account::Account,
+    client::{Client, RawClientId},
+    ledger::Ledger,
+    transaction::{
+        deposit::Deposit, withdrawal::Withdrawal, RawTransactionId, Timestamp, TransactionId,
+        Transfer,
+    },
+    transaction_record::TransactionRecord,
+};
+
+// Amounts are stored as text to keep them exact, use `CAST(amount AS REAL)` for arithmetic in SQL.
+// Ids are stored by their bits as SQLite integers are signed, so ids above `i64::MAX` read as negative in SQL.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS accounts (
+        client INTEGER PRIMARY KEY,
+        available TEXT NOT NULL,
+        held TEXT NOT NULL,
+        pending TEXT NOT NULL,
+        total TEXT NOT NULL,
+        locked INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS transactions (
+        tx INTEGER PRIMARY KEY,
+        type TEXT NOT NULL,
+        client INTEGER NOT NULL,
+        amount TEXT NOT NULL,
+        timestamp INTEGER,
+        pending INTEGER NOT NULL,
+        under_dispute INTEGER NOT NULL,
+        charge_backed INTEGER NOT NULL,
+        disputed_at INTEGER,
+        foreign_client INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS progress (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        rows INTEGER NOT NULL
+    );";
+
+const RECORD_COLUMNS: &str =
+    "tx, type, client, amount, timestamp, pending, under_dispute, charge_backed, disputed_at, foreign_client";
+
+fn lock(conn: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+    conn.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn decimal(row: &Row, index: usize) -> rusqlite::Result<Decimal> {
+    let text: String = row.get(index)?;
+    Decimal::from_str(&text)
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(err)))
+}
+
+/// Reads a record from a row with the [`RECORD_COLUMNS`].
+fn record(row: &Row) -> rusqlite::Result<TransactionRecord> {
+    let id = TransactionId::new(row.get::<_, i64>(0)? as RawTransactionId);
+    let kind: String = row.get(1)?;
+    let client = Client::new(row.get::<_, i64>(2)? as RawClientId);
+    let amount = decimal(row, 3)?;
+    let timestamp = row.get::<_, Option<u64>>(4)?.map(Timestamp::from_secs);
+    let tx = match kind.as_str() {
+        "deposit" => Transfer::Deposit(Deposit::new(client, id, amount).with_timestamp(timestamp)),
+        "withdrawal" => {
+            Transfer::Withdrawal(Withdrawal::new(client, id, amount).with_timestamp(timestamp))
+        }
+        _ => {
+            return Err(rusqlite::Error::FromSqlConversionFailure(
+                1,
+                Type::Text,
+                format!("Unknown transfer type {kind}").into(),
+            ))
+        }
+    };
+    Ok(TransactionRecord::from_parts(
+        tx,
+        row.get(6)?,
+        row.get(7)?,
+        row.get::<_, Option<u64>>(8)?.map(Timestamp::from_secs),
+        row.get::<_, Option<i64>>(9)?
+            .map(|client| Client::new(client as RawClientId)),
+    )
+    .with_pending(row.get(5)?))
+}
+
+/// Represents a SQLite database that persists the accounts and transaction records of a run.
+///
+/// All changes are written in a database transaction that is committed at every [`SqliteStore::checkpoint`],
+/// together with the number of input rows handled so far.
+/// A run that is stopped loses the changes since the last checkpoint and resumes from there.
+/// The database can be queried with SQL at any time, it only shows the committed state.
+#[derive(Debug)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+    // Accounts as of the last checkpoint, so only changed accounts are written
+    saved: HashMap<Client, Account>,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it when it does not exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        // Lets analysts read the database while a run is writing to it
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute_batch(SCHEMA)?;
+        conn.execute_batch("BEGIN")?;
+
+        let mut store = Self {
+            conn: Arc::new(Mutex::new(conn)),
+            saved: HashMap::new(),
+        };
+        store.saved = store
+            .accounts()?
+            .into_iter()
+            .map(|account| (account.client(), account))
+            .collect();
+        Ok(store)
+    }
+
+    /// Returns a ledger that stores its records in the database.
+    pub fn ledger(&self) -> rusqlite::Result<SqliteLedger> {
+        let len: usize =
+            lock(&self.conn)
+                .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))?;
+        Ok(SqliteLedger {
+            conn: Arc::clone(&self.conn),
+            len,
+        })
+    }
+
+    /// Returns the accounts as of the last checkpoint ordered by client.
+    pub fn accounts(&self) -> rusqlite::Result<Vec<Account>> {
+        let conn = lock(&self.conn);
+        let mut stmt = conn.prepare_cached(
+            "SELECT client, available, held, pending, locked FROM accounts ORDER BY client",
+        )?;
+        let accounts = stmt.query_map([], |row| {
+            Ok(Account::from_parts(
+                Client::new(row.get::<_, i64>(0)? as RawClientId),
+                decimal(row, 1)?,
+                decimal(row, 2)?,
+                decimal(row, 3)?,
+                row.get(4)?,
+            ))
+        })?;
+        accounts.collect()
+    }
+
+    /// Returns the number of input rows handled as of the last checkpoint.
+    pub fn rows(&self) -> rusqlite::Result<u64> {
+        let rows = lock(&self.conn)
+            .query_row("SELECT rows FROM progress WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(rows.unwrap_or_default())
+    }
+
+    /// Writes the accounts that changed since the last checkpoint and commits all changes.
+    ///
+    /// `rows` is the number of input rows handled so far, where a resumed run continues.
+    pub fn checkpoint(&mut self, accounts: &[Account], rows: u64) -> rusqlite::Result<()> {
+        let conn = lock(&self.conn);
+        {
+            let mut stmt = conn.prepare_cached(
+                "INSERT OR REPLACE INTO accounts (client, available, held, pending, total, locked)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for account in accounts {
+                if self.saved.get(&account.client()) == Some(account) {
+                    continue;
+                }
+                stmt.execute(params![
+                    account.client().id() as i64,
+                    account.available().to_string(),
+                    account.held().to_string(),
+                    account.pending().to_string(),
+                    account.total().to_string(),
+                    account.locked(),
+                ])?;
+                self.saved.insert(account.client(), account.clone());
+            }
+        }
+        conn.execute(
+            "INSERT OR REPLACE INTO progress (id, rows) VALUES (0, ?1)",
+            [rows],
+        )?;
+        conn.execute_batch("COMMIT; BEGIN")
+    }
+}
+
+/// Represents a ledger that keeps the transaction records in the database of a [`SqliteStore`].
+///
+/// Writes become durable at the next checkpoint of the store.
+///
+/// # Panics
+/// Reading and writing the database is not expected to fail; any SQLite error panics.
+#[derive(Debug)]
+pub struct SqliteLedger {
+    conn: Arc<Mutex<Connection>>,
+    len: usize,
+}
+
+impl Ledger for SqliteLedger {
+    fn contains(&self, id: TransactionId) -> bool {
+        let conn = lock(&self.conn);
+        let mut stmt = conn
+            .prepare_cached("SELECT 1 FROM transactions WHERE tx = ?1")
+            .expect("Could not query the transactions");
+        stmt.exists([id.id() as i64])
+            .expect("Could not query the transactions")
+    }
+
+    fn get(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        let conn = lock(&self.conn);
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "SELECT {RECORD_COLUMNS} FROM transactions WHERE tx = ?1"
+            ))
+            .expect("Could not query the transactions");
+        stmt.query_row([id.id() as i64], record)
+            .optional()
+            .expect("Could not read a transaction")
+    }
+
+    fn insert(&mut self, record: TransactionRecord) {
+        if !self.contains(record.tx().transaction_id()) {
+            self.len += 1;
+        }
+        let tx = record.tx();
+        let kind = match tx {
+            Transfer::Deposit(_) => "deposit",
+            Transfer::Withdrawal(_) => "withdrawal",
+        };
+        let conn = lock(&self.conn);
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "INSERT OR REPLACE INTO transactions ({RECORD_COLUMNS})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+            ))
+            .expect("Could not write the transactions");
+        stmt.execute(params![
+            tx.transaction_id().id() as i64,
+            kind,
+            tx.client().id() as i64,
+            tx.amount().to_string(),
+            tx.timestamp().map(|timestamp| timestamp.as_secs()),
+            record.pending(),
+            record.under_dispute(),
+            record.charge_backed(),
+            record.disputed_at().map(|at| at.as_secs()),
+            record.foreign_client().map(|client| client.id() as i64),
+        ])
+        .expect("Could not write a transaction");
+    }
+
+    fn remove(&mut self, id: TransactionId) -> Option<TransactionRecord> {
+        let record = self.get(id)?;
+        lock(&self.conn)
+            .execute("DELETE FROM transactions WHERE tx = ?1", [id.id() as i64])
+            .expect("Could not delete a transaction");
+        self.len -= 1;
+        Some(record)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn records(&self) -> Box<dyn Iterator<Item = TransactionRecord> + '_> {
+        let conn = lock(&self.conn);
+        let mut stmt = conn
+            .prepare_cached(&format!("SELECT {RECORD_COLUMNS} FROM transactions"))
+            .expect("Could not query the transactions");
+        let records: Vec<TransactionRecord> = stmt
+            .query_map([], record)
+            .and_then(|records| records.collect())
+            .expect("Could not read the transactions");
+        Box::new(records.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteStore;
+    use crate::{
+        ledger::{tests::test_ledger, Ledger},
+        transaction::{transactions, Transaction},
+        trial_balance::TrialBalance,
+    };
+
+    /// Returns the path of a fresh database for the test.
+    fn path(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("csv-reader-{}-{name}.db", std::process::id()));
+        remove(&path);
+        path
+    }
+
+    fn remove(path: &std::path::Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+
+    #[test]
+    fn test_sqlite_ledger() {
+        let path = path("ledger");
+        let store = SqliteStore::open(&path).unwrap();
+        test_ledger(store.ledger().unwrap());
+        drop(store);
+        remove(&path);
+    }
+
+    #[test]
+    fn test_sqlite_resume() {
+        let data = "type, client, tx, amount
+            deposit, 1, 1, 10.5
+            deposit, 2, 2, 20
+            dispute, 1, 1,
+            withdrawal, 2, 3, 5
+            deposit, 1, 4, 1.25
+            resolve, 1, 1,
+            dispute, 2, 2,
+            chargeback, 2, 2,
+            deposit, 2, 5, 3";
+        let transactions: Vec<Transaction> =
+            transactions(data.as_bytes()).map(Result::unwrap).collect();
+        let mut expected = TrialBalance::new();
+        for tx in transactions.clone() {
+            let _ = expected.handle_transaction(tx);
+        }
+
+        let path = path("resume");
+        {
+            let mut store = SqliteStore::open(&path).unwrap();
+            let mut trial_balance = TrialBalance::new().with_ledger(store.ledger().unwrap());
+            for (rows, tx) in transactions.iter().cloned().enumerate().take(6) {
+                let _ = trial_balance.handle_transaction(tx);
+                if rows == 3 {
+                    store.checkpoint(&trial_balance.snapshot(), 4).unwrap();
+                }
+            }
+            // Stopped without a checkpoint, the last two rows are lost
+        }
+
+        let mut store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.rows().unwrap(), 4);
+        let ledger = store.ledger().unwrap();
+        assert_eq!(ledger.len(), 3);
+        let mut trial_balance = TrialBalance::new()
+            .with_ledger(ledger)
+            .with_accounts(store.accounts().unwrap());
+        for tx in transactions.into_iter().skip(4) {
+            let _ = trial_balance.handle_transaction(tx);
+        }
+        store.checkpoint(&trial_balance.snapshot(), 9).unwrap();
+
+        assert_eq!(trial_balance.snapshot(), expected.snapshot());
+        assert_eq!(trial_balance.verify(), Ok(()));
+        assert_eq!(store.accounts().unwrap(), expected.snapshot());
+        drop((store, trial_balance));
+        remove(&path);
+    }
+}
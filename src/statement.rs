@@ -0,0 +1,158 @@
+use rust_decimal::Decimal;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+use crate::{
+    account::{round, Account},
+    error::TransactionError,
+    transaction::Transaction,
+};
+
+/// Represents a single line on the statement of a client.
+///
+/// Every transaction handled for the client results in a line, whether it was applied or rejected.
+/// The balances are those of the account directly after the transaction was handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementLine {
+    seq: u64,
+    tx: Transaction,
+    amount: Option<Decimal>,
+    result: Result<(), TransactionError>,
+    available: Decimal,
+    held: Decimal,
+    pending: Decimal,
+    locked: bool,
+}
+
+impl StatementLine {
+    pub fn new(
+        seq: u64,
+        tx: Transaction,
+        amount: Option<Decimal>,
+        result: Result<(), TransactionError>,
+        account: &Account,
+    ) -> Self {
+        Self {
+            seq,
+            tx,
+            amount,
+            result,
+            available: account.available(),
+            held: account.held(),
+            pending: account.pending(),
+            locked: account.locked(),
+        }
+    }
+
+    /// Returns the position of the transaction in the order it was handled.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn tx(&self) -> &Transaction {
+        &self.tx
+    }
+
+    /// Returns the amount of the transfer, or of the transfer that was mutated.
+    pub fn amount(&self) -> Option<Decimal> {
+        self.amount
+    }
+
+    pub fn result(&self) -> &Result<(), TransactionError> {
+        &self.result
+    }
+
+    pub fn available(&self) -> Decimal {
+        self.available
+    }
+
+    pub fn held(&self) -> Decimal {
+        self.held
+    }
+
+    pub fn pending(&self) -> Decimal {
+        self.pending
+    }
+
+    pub fn total(&self) -> Decimal {
+        self.available + self.held
+    }
+}
+
+impl Serialize for StatementLine {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("StatementLine", 11)?;
+        s.serialize_field("seq", &self.seq)?;
+        s.serialize_field("type", &self.tx.transaction_type())?;
+        s.serialize_field("client", &self.tx.client())?;
+        s.serialize_field("tx", &self.tx.transaction_id())?;
+        s.serialize_field("amount", &self.amount.map(round))?;
+        s.serialize_field("available", &round(self.available))?;
+        s.serialize_field("held", &round(self.held))?;
+        s.serialize_field("pending", &round(self.pending))?;
+        s.serialize_field("total", &round(self.total()))?;
+        s.serialize_field("locked", &self.locked)?;
+        s.serialize_field(
+            "rejection",
+            &self.result.as_ref().err().map(|err| err.to_string()),
+        )?;
+        s.end()
+    }
+}
+
+/// Writes the statement lines as csv.
+pub fn to_csv<W>(lines: &[StatementLine], w: &mut W) -> Result<(), csv::Error>
+where
+    W: std::io::Write,
+{
+    let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(w);
+    lines.iter().try_for_each(|line| wtr.serialize(line))?;
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_csv;
+    use crate::{client::Client, transaction::transactions, trial_balance::TrialBalance};
+
+    #[test]
+    fn test_statement() {
+        let data = "type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.0
+withdrawal,1,3,20.0
+dispute,1,1,
+withdrawal,1,4,1.0
+chargeback,1,1,
+deposit,1,1,3.0";
+        let mut trial_balance = TrialBalance::new().with_history();
+        for tx in transactions(data.as_bytes()) {
+            let _ = trial_balance.handle_transaction(tx.unwrap());
+        }
+
+        let statement = trial_balance.statement(Client::new(1)).unwrap();
+        assert_eq!(statement.len(), 6);
+        assert!(statement
+            .iter()
+            .all(|line| line.tx().client() == Client::new(1)));
+
+        let mut output = Vec::new();
+        to_csv(statement, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "seq,type,client,tx,amount,available,held,pending,total,locked,rejection
+0,deposit,1,1,10,10,0,0,10,false,
+2,withdrawal,1,3,20,10,0,0,10,false,Error: Insufficient funds
+3,dispute,1,1,10,0,10,0,10,false,
+4,withdrawal,1,4,1,0,10,0,10,false,Error: Insufficient funds
+5,chargeback,1,1,10,0,0,0,0,true,
+6,deposit,1,1,3,0,0,0,0,true,Error: Duplicate transaction TransactionId(1)
+"
+        );
+        assert_eq!(trial_balance.statement(Client::new(3)), Some(&[][..]));
+        assert_eq!(TrialBalance::new().statement(Client::new(1)), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{collections::BTreeMap, fmt, time::Instant};
+
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::{
+    account::round,
+    error::TransactionError,
+    observer::Balances,
+    transaction::{Transaction, TransactionType},
+};
+
+/// Represents the outcomes of the transactions of a single type.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TypeStats {
+    pub read: u64,
+    pub applied: u64,
+    pub rejected: u64,
+    /// Number of rejections per kind of [`TransactionError`].
+    pub errors: BTreeMap<&'static str, u64>,
+}
+
+/// Represents the statistics of a run, as collected by [`TrialBalance::with_stats`].
+///
+/// Amounts are the sums over the applied transactions: `held` is what disputes moved into held funds
+/// and `charged_back` what charge backs took out of them.
+///
+/// [`TrialBalance::with_stats`]: crate::trial_balance::TrialBalance::with_stats
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Stats {
+    /// Outcomes per type of transaction, keyed by the name of the type.
+    pub types: BTreeMap<&'static str, TypeStats>,
+    /// Number of rows that could not be read as a transaction.
+    pub invalid: u64,
+    pub deposited: Decimal,
+    pub withdrawn: Decimal,
+    pub held: Decimal,
+    pub charged_back: Decimal,
+    pub locked_accounts: usize,
+    /// Seconds between the first transaction and the moment the statistics were taken.
+    pub seconds: f64,
+    pub rows_per_second: f64,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of rows read, including the invalid ones.
+    pub fn rows(&self) -> u64 {
+        self.invalid + self.types.values().map(|stats| stats.read).sum::<u64>()
+    }
+
+    /// Counts the outcome of the transaction, `before` and `after` are the balances of its account.
+    pub fn record(
+        &mut self,
+        tx: &Transaction,
+        res: &Result<(), TransactionError>,
+        before: Balances,
+        after: Balances,
+    ) {
+        self.started.get_or_insert_with(Instant::now);
+        let transaction_type = tx.transaction_type();
+        let stats = self.types.entry(transaction_type.name()).or_default();
+        stats.read += 1;
+        match res {
+            Ok(()) => stats.applied += 1,
+            Err(err) => {
+                stats.rejected += 1;
+                *stats.errors.entry(err.kind()).or_default() += 1;
+                return;
+            }
+        }
+        match (transaction_type, tx) {
+            (TransactionType::Deposit, Transaction::Transfer(transfer)) => {
+                self.deposited += transfer.amount();
+            }
+            (TransactionType::Withdrawal, Transaction::Transfer(transfer)) => {
+                self.withdrawn += transfer.amount();
+            }
+            (TransactionType::Dispute, _) => self.held += after.held - before.held,
+            (TransactionType::ChargeBack, _) => self.charged_back += before.held - after.held,
+            _ => {}
+        }
+    }
+
+    /// Counts a row that could not be read as a transaction.
+    pub fn record_invalid(&mut self) {
+        self.started.get_or_insert_with(Instant::now);
+        self.invalid += 1;
+    }
+
+    /// Adds the statistics of another trial balance, like another shard of the same run.
+    pub fn merge(&mut self, other: &Stats) {
+        for (name, other) in &other.types {
+            let stats = self.types.entry(name).or_default();
+            stats.read += other.read;
+            stats.applied += other.applied;
+            stats.rejected += other.rejected;
+            for (kind, count) in &other.errors {
+                *stats.errors.entry(kind).or_default() += count;
+            }
+        }
+        self.invalid += other.invalid;
+        self.deposited += other.deposited;
+        self.withdrawn += other.withdrawn;
+        self.held += other.held;
+        self.charged_back += other.charged_back;
+        self.locked_accounts += other.locked_accounts;
+        self.started = match (self.started, other.started) {
+            (Some(started), Some(other)) => Some(started.min(other)),
+            (started, other) => started.or(other),
+        };
+        self.seconds = self.seconds.max(other.seconds);
+        self.rows_per_second = throughput(self.rows(), self.seconds);
+    }
+
+    /// Sets the number of locked accounts and the throughput up to now.
+    pub(crate) fn finish(&mut self, locked_accounts: usize) {
+        self.locked_accounts = locked_accounts;
+        self.seconds = self
+            .started
+            .map_or(0.0, |started| started.elapsed().as_secs_f64());
+        self.rows_per_second = throughput(self.rows(), self.seconds);
+    }
+}
+
+fn throughput(rows: u64, seconds: f64) -> f64 {
+    if seconds > 0.0 {
+        rows as f64 / seconds
+    } else {
+        0.0
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "rows: {} ({} invalid)", self.rows(), self.invalid)?;
+        for (name, stats) in &self.types {
+            writeln!(
+                f,
+                "{name}: {} read, {} applied, {} rejected",
+                stats.read, stats.applied, stats.rejected
+            )?;
+            for (kind, count) in &stats.errors {
+                writeln!(f, "  {kind}: {count}")?;
+            }
+        }
+        writeln!(f, "deposited: {}", round(self.deposited))?;
+        writeln!(f, "withdrawn: {}", round(self.withdrawn))?;
+        writeln!(f, "held: {}", round(self.held))?;
+        writeln!(f, "charged back: {}", round(self.charged_back))?;
+        writeln!(f, "locked accounts: {}", self.locked_accounts)?;
+        writeln!(
+            f,
+            "throughput: {:.0} rows/s over {:.3}s",
+            self.rows_per_second, self.seconds
+        )
+    }
+}
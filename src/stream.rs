@@ -0,0 +1,115 @@
+use futures_util::{Stream, StreamExt};
+
+use crate::{
+    error::TransactionError,
+    transaction::{error::DeserializationError, Transaction, TransactionRow},
+    trial_balance::TrialBalance,
+};
+
+/// Represents an item of an input stream that can be handled by the trial balance.
+pub trait IntoTransaction {
+    fn into_transaction(self) -> Result<Transaction, DeserializationError>;
+}
+
+impl IntoTransaction for Transaction {
+    fn into_transaction(self) -> Result<Transaction, DeserializationError> {
+        Ok(self)
+    }
+}
+
+impl IntoTransaction for TransactionRow {
+    fn into_transaction(self) -> Result<Transaction, DeserializationError> {
+        Transaction::try_from(self)
+    }
+}
+
+impl<T, E> IntoTransaction for Result<T, E>
+where
+    T: IntoTransaction,
+    DeserializationError: From<E>,
+{
+    fn into_transaction(self) -> Result<Transaction, DeserializationError> {
+        self?.into_transaction()
+    }
+}
+
+/// Represents what happened to a single item of the input stream.
+#[derive(Debug)]
+pub enum Outcome {
+    /// The transaction has been applied to the trial balance.
+    Applied(Transaction),
+    /// The trial balance rejected the transaction.
+    Rejected(Transaction, TransactionError),
+    /// The item could not be turned into a transaction.
+    Invalid(DeserializationError),
+}
+
+/// Handles a stream of transactions and yields the outcome of every item in order.
+///
+/// Each item is handled when the outcome is polled, so the runtime is never blocked for longer than a single transaction.
+/// The trial balance holds the final state once the stream has been consumed.
+pub fn process<'a, S>(
+    trial_balance: &'a mut TrialBalance,
+    transactions: S,
+) -> impl Stream<Item = Outcome> + 'a
+where
+    S: Stream + 'a,
+    S::Item: IntoTransaction,
+{
+    transactions.map(move |item| match item.into_transaction() {
+        Ok(tx) => match trial_balance.handle_transaction(tx.clone()) {
+            Ok(()) => Outcome::Applied(tx),
+            Err(err) => Outcome::Rejected(tx, err),
+        },
+        Err(err) => Outcome::Invalid(err),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{stream, StreamExt};
+
+    use super::{process, Outcome};
+    use crate::{error::TransactionError, transaction, trial_balance::TrialBalance};
+
+    #[tokio::test]
+    async fn test_process() {
+        let data = "type, client, tx, amount\n\
+            deposit, 1, 1, 1.0\n\
+            withdrawal, 1, 2, 2.0\n\
+            deposit, 1, 1, 1.0\n\
+            transfer, 1, 3, 1.0\n";
+
+        let mut trial_balance = TrialBalance::new();
+        let outcomes: Vec<Outcome> = process(
+            &mut trial_balance,
+            transaction::async_reader::transactions(data.as_bytes()),
+        )
+        .collect()
+        .await;
+
+        assert!(matches!(outcomes[0], Outcome::Applied(_)));
+        assert!(matches!(
+            outcomes[1],
+            Outcome::Rejected(_, TransactionError::InsufficientFunds)
+        ));
+        assert!(matches!(
+            outcomes[2],
+            Outcome::Rejected(_, TransactionError::DuplicateTransaction(_))
+        ));
+        assert!(matches!(outcomes[3], Outcome::Invalid(_)));
+        assert_eq!(outcomes.len(), 4);
+
+        // Transactions that have already been parsed are accepted as well
+        let transactions: Vec<_> = transaction::transactions(data.as_bytes())
+            .filter_map(Result::ok)
+            .collect();
+        let mut sequential = TrialBalance::new();
+        let applied = process(&mut sequential, stream::iter(transactions))
+            .filter(|outcome| std::future::ready(matches!(outcome, Outcome::Applied(_))))
+            .count()
+            .await;
+        assert_eq!(applied, 1);
+        assert_eq!(sequential.snapshot(), trial_balance.snapshot());
+    }
+}
@@ -0,0 +1,115 @@
+use futures_util::{stream, Stream, StreamExt};
+use tokio::io::AsyncRead;
+
+use super::{
+    error::DeserializationError,
+    splitter::{self, Splitter},
+    Transaction, TransactionRow,
+};
+
+struct State<R> {
+    reader: R,
+    splitter: Splitter,
+    headers: Option<csv::ByteRecord>,
+    rows: std::vec::IntoIter<Result<TransactionRow, csv::Error>>,
+    failed: bool,
+}
+
+/// Reads the rows of an asynchronous input like [`transaction_reader`](super::transaction_reader).
+///
+/// Records are yielded as soon as they are complete, so rows arriving over a socket are not held back.
+pub fn transaction_reader<R>(reader: R) -> impl Stream<Item = Result<TransactionRow, csv::Error>>
+where
+    R: AsyncRead + Unpin,
+{
+    let state = State {
+        reader,
+        splitter: Splitter::new(),
+        headers: None,
+        rows: Vec::new().into_iter(),
+        failed: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(row) = state.rows.next() {
+                return Some((row, state));
+            }
+            if state.failed {
+                return None;
+            }
+            // The first record holds the headers
+            let max_records = if state.headers.is_some() { u64::MAX } else { 1 };
+            match state.splitter.split(max_records, 1) {
+                Some((data, position)) => match &state.headers {
+                    Some(headers) => {
+                        state.rows = splitter::parse(data, position, headers).into_iter();
+                    }
+                    None => state.headers = Some(splitter::headers(data)),
+                },
+                None if state.splitter.done() => return None,
+                None => {
+                    if let Err(err) = state.splitter.fill_async(&mut state.reader).await {
+                        state.failed = true;
+                        return Some((Err(csv::Error::from(err)), state));
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Reads the rows like [`transaction_reader`] and converts each of them into a [`Transaction`].
+pub fn transactions<R>(reader: R) -> impl Stream<Item = Result<Transaction, DeserializationError>>
+where
+    R: AsyncRead + Unpin,
+{
+    transaction_reader(reader).map(|tx_row| Transaction::try_from(tx_row?))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    use super::transactions;
+
+    #[tokio::test]
+    async fn test_transactions_match_sync_reader() {
+        let data = "type, client, tx, amount\n\
+            deposit, 1, 1, 1.0\r\n\
+            \n\
+            withdrawal, 1, 2,\n\
+            \"deposit\", \"2\", 3, \"2.5\"\n\
+            transfer, 1, 4, 1.0\n\
+            dispute, 1, 1";
+
+        let expected: Vec<String> = crate::transaction::transactions(data.as_bytes())
+            .map(|tx| format!("{tx:?}"))
+            .collect();
+        let parsed: Vec<String> = transactions(data.as_bytes())
+            .map(|tx| format!("{tx:?}"))
+            .collect()
+            .await;
+        assert_eq!(parsed, expected);
+    }
+
+    #[tokio::test]
+    async fn test_transactions_before_end_of_input() {
+        let (reader, mut writer) = tokio::io::duplex(64);
+        let mut transactions = Box::pin(transactions(reader));
+
+        writer
+            .write_all(b"type, client, tx, amount\ndeposit, 1, 1, 1.0\n")
+            .await
+            .unwrap();
+        // The deposit is complete while the input is still open
+        let tx = transactions.next().await.unwrap().unwrap();
+        assert_eq!(tx.client().id(), 1);
+
+        writer.write_all(b"deposit, 2, 2, 1.0").await.unwrap();
+        drop(writer);
+        let tx = transactions.next().await.unwrap().unwrap();
+        assert_eq!(tx.client().id(), 2);
+        assert!(transactions.next().await.is_none());
+    }
+}
@@ -1,17 +1,22 @@
 use serde::{Deserialize, Serialize};
 
-use super::TransactionId;
+use super::{Timestamp, TransactionId};
 use crate::client::Client;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct ChargeBack {
     client: Client,
     tx: TransactionId,
+    timestamp: Option<Timestamp>,
 }
 
 impl ChargeBack {
     pub fn new(client: Client, tx: TransactionId) -> Self {
-        Self { client, tx }
+        Self {
+            client,
+            tx,
+            timestamp: None,
+        }
     }
     pub fn client(&self) -> Client {
         self.client
@@ -19,4 +24,11 @@ impl ChargeBack {
     pub fn transaction_id(&self) -> TransactionId {
         self.tx
     }
+    pub fn with_timestamp(mut self, timestamp: Option<Timestamp>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
 }
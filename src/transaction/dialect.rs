@@ -0,0 +1,388 @@
+use std::{collections::BTreeMap, path::Path};
+
+use csv::StringRecord;
+use serde::Deserialize;
+
+use super::{
+    error::DeserializationError,
+    interner::{self, SharedInterner, TxIds},
+    reader_builder, Transaction, TransactionRow, TransactionType,
+};
+use crate::error::DialectError;
+
+// Columns of a row in the order of `TransactionRow`.
+const COLUMNS: [&str; 5] = ["type", "client", "tx", "amount", "timestamp"];
+// Columns every input must have, the amount and timestamp may be left out.
+const REQUIRED: usize = 3;
+const TYPE: usize = 0;
+const TX: usize = 2;
+const AMOUNT: usize = 3;
+
+/// Represents the names of the columns of an input.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Columns {
+    #[serde(rename = "type")]
+    pub transaction_type: String,
+    pub client: String,
+    pub tx: String,
+    pub amount: String,
+    pub timestamp: String,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        let [transaction_type, client, tx, amount, timestamp] = COLUMNS.map(String::from);
+        Self {
+            transaction_type,
+            client,
+            tx,
+            amount,
+            timestamp,
+        }
+    }
+}
+
+impl Columns {
+    fn names(&self) -> [&str; 5] {
+        [
+            &self.transaction_type,
+            &self.client,
+            &self.tx,
+            &self.amount,
+            &self.timestamp,
+        ]
+    }
+}
+
+/// Represents how the rows of an input are written, the default reads the usual format.
+///
+/// Labels in `types` are translated into their transaction type, other labels are read as usual.
+/// Transaction ids that are strings or UUIDs are interned as numeric ids, see [`Interner`](super::interner::Interner).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Dialect {
+    pub delimiter: char,
+    pub quote: char,
+    /// Whether quotes are recognized, when not they are part of the field.
+    pub quoting: bool,
+    pub decimal_separator: char,
+    pub columns: Columns,
+    pub types: BTreeMap<String, TransactionType>,
+    pub tx_ids: TxIds,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            quoting: true,
+            decimal_separator: '.',
+            columns: Columns::default(),
+            types: BTreeMap::new(),
+            tx_ids: TxIds::Numeric,
+        }
+    }
+}
+
+impl Dialect {
+    /// Reads the transactions of the input written in this dialect.
+    pub fn transactions<R>(&self, reader: R) -> DialectTransactions<R>
+    where
+        R: std::io::Read,
+    {
+        // Characters are checked to be ASCII when the profiles are loaded
+        let reader = reader_builder()
+            .delimiter(self.delimiter as u8)
+            .quote(self.quote as u8)
+            .quoting(self.quoting)
+            .from_reader(reader);
+        DialectTransactions {
+            reader,
+            dialect: self.clone(),
+            positions: None,
+            record: StringRecord::new(),
+            headers: StringRecord::from(COLUMNS.to_vec()),
+            interner: (self.tx_ids != TxIds::Numeric).then(SharedInterner::default),
+            done: false,
+        }
+    }
+
+    fn check(&self, profile: &str) -> Result<(), DialectError> {
+        let fields = [
+            ("delimiter", self.delimiter),
+            ("quote", self.quote),
+            ("decimal_separator", self.decimal_separator),
+        ];
+        match fields.into_iter().find(|(_, c)| !c.is_ascii()) {
+            Some((field, _)) => Err(DialectError::NotAscii {
+                profile: profile.to_string(),
+                field,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Represents named dialects as loaded from a TOML file:
+///
+/// ```toml
+/// [profiles.partner]
+/// delimiter = ";"
+/// decimal_separator = ","
+///
+/// [profiles.partner.columns]
+/// type = "kind"
+/// client = "account_id"
+/// tx = "txn_id"
+/// amount = "value"
+///
+/// [profiles.partner.types]
+/// DEP = "deposit"
+/// WDL = "withdrawal"
+/// CB = "chargeback"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Dialect>,
+}
+
+impl Profiles {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DialectError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, DialectError> {
+        let profiles: Self = toml::from_str(s)?;
+        for (name, dialect) in &profiles.profiles {
+            dialect.check(name)?;
+        }
+        Ok(profiles)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Dialect> {
+        self.profiles.get(name)
+    }
+}
+
+/// Represents the transactions of an input written in a [`Dialect`].
+///
+/// Each row is rewritten into the usual columns before it is converted, so errors are the same as
+/// those of [`transactions`](super::transactions) and keep the position of the row.
+pub struct DialectTransactions<R> {
+    reader: csv::Reader<R>,
+    dialect: Dialect,
+    // Position in the input of each of the columns, once the header is read
+    positions: Option<[Option<usize>; 5]>,
+    record: StringRecord,
+    headers: StringRecord,
+    interner: Option<SharedInterner>,
+    done: bool,
+}
+
+impl<R: std::io::Read> DialectTransactions<R> {
+    /// Returns the interner of the string ids, unless the ids are numeric.
+    pub fn interner(&self) -> Option<SharedInterner> {
+        self.interner.clone()
+    }
+
+    fn positions(&mut self) -> Result<[Option<usize>; 5], DeserializationError> {
+        let headers = self.reader.headers()?;
+        let names = self.dialect.columns.names();
+        let positions = names.map(|name| headers.iter().position(|header| header == name));
+        match positions[..REQUIRED].iter().position(Option::is_none) {
+            Some(missing) => Err(DeserializationError::MissingColumn(
+                names[missing].to_string(),
+            )),
+            None => Ok(positions),
+        }
+    }
+
+    fn row(&self, positions: &[Option<usize>; 5]) -> Result<TransactionRow, DeserializationError> {
+        let mut fields = positions.map(|position| {
+            position
+                .and_then(|position| self.record.get(position))
+                .unwrap_or_default()
+                .to_string()
+        });
+        if let Some(transaction_type) = self.dialect.types.get(&fields[TYPE]) {
+            fields[TYPE] = transaction_type.name().to_string();
+        }
+        if let Some(interner) = &self.interner {
+            let id = interner::lock(interner).intern(&fields[TX], self.dialect.tx_ids)?;
+            fields[TX] = id.id().to_string();
+        }
+        if self.dialect.decimal_separator != '.' {
+            fields[AMOUNT] = fields[AMOUNT].replace(self.dialect.decimal_separator, ".");
+        }
+        let mut record = StringRecord::from(fields.to_vec());
+        record.set_position(self.record.position().cloned());
+        Ok(record.deserialize(Some(&self.headers))?)
+    }
+}
+
+impl<R: std::io::Read> Iterator for DialectTransactions<R> {
+    type Item = Result<Transaction, DeserializationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let positions = match self.positions {
+            Some(positions) => positions,
+            None => match self.positions() {
+                Ok(positions) => *self.positions.insert(positions),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            },
+        };
+        match self.reader.read_record(&mut self.record) {
+            Ok(true) => Some(self.row(&positions).and_then(Transaction::try_from)),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dialect, Profiles};
+    use crate::{
+        transaction::{error::DeserializationError, interner::TxIds, transactions},
+        trial_balance::TrialBalance,
+    };
+
+    const PROFILES: &str = r#"
+[profiles.partner]
+delimiter = ";"
+decimal_separator = ","
+
+[profiles.partner.columns]
+type = "kind"
+client = "account_id"
+tx = "txn_id"
+amount = "value"
+
+[profiles.partner.types]
+DEP = "deposit"
+WDL = "withdrawal"
+CB = "chargeback"
+DSP = "dispute"
+"#;
+
+    #[test]
+    fn test_dialect() {
+        let usual = "type, client, tx, amount
+deposit, 1, 1, 1.5
+withdrawal, 1, 2, 0.25
+dispute, 1, 1,
+chargeback, 1, 1,";
+        // Columns in another order, quoted amounts and a column that is not read
+        let partner = "note; value; txn_id; account_id; kind
+a;\"1,5\"; 1; 1; DEP
+b; 0,25; 2; 1; WDL
+c; ; 1; 1; DSP
+d; ; 1; 1; chargeback";
+        let profiles = Profiles::from_toml(PROFILES).unwrap();
+        let dialect = profiles.get("partner").unwrap();
+        let expected: Vec<_> = transactions(usual.as_bytes()).map(Result::unwrap).collect();
+        let read: Vec<_> = dialect
+            .transactions(partner.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read, expected);
+
+        let default: Vec<_> = Dialect::default()
+            .transactions(usual.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(default, expected);
+    }
+
+    #[test]
+    fn test_dialect_errors() {
+        let dialect = Profiles::from_toml(PROFILES).unwrap().profiles["partner"].clone();
+        let read = |data: &str| dialect.transactions(data.as_bytes()).collect::<Vec<_>>();
+
+        let missing = read("kind;value;account_id\nDEP;1;1");
+        assert!(matches!(
+            &missing[..],
+            [Err(DeserializationError::MissingColumn(column))] if column == "txn_id"
+        ));
+        // An unknown label keeps the position of its row
+        let unknown = read("kind;account_id;txn_id;value\nDEP;1;1;1\nXFR;1;2;1");
+        assert!(unknown[0].is_ok());
+        match &unknown[1] {
+            Err(DeserializationError::Csv(err)) => {
+                assert_eq!(err.position().map(|position| position.line()), Some(3))
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+
+        assert!(Profiles::from_toml("[profiles.x]\ndelimiter = \"§\"").is_err());
+        assert!(Profiles::from_toml("[profiles.x]\nseparator = \";\"").is_err());
+    }
+
+    #[test]
+    fn test_dialect_string_ids() {
+        let numeric = "type,client,tx,amount
+deposit,1,1,10
+deposit,1,1,5
+deposit,2,2,4
+dispute,1,1,
+dispute,2,3,
+chargeback,1,1,
+withdrawal,2,4,1";
+        let uuids = "type,client,tx,amount
+deposit,1,0b0e2a3c-8d3c-4a52-b8c4-5f1e0f0c6a01,10
+deposit,1,0B0E2A3C8D3C4A52B8C45F1E0F0C6A01,5
+deposit,2,7d4f0e53-54c3-4b8c-9f7e-2c3a1b0d9e02,4
+dispute,1,0b0e2a3c-8d3c-4a52-b8c4-5f1e0f0c6a01,
+dispute,2,9a1b2c3d-4e5f-4061-8273-94a5b6c7d803,
+chargeback,1,0b0e2a3c-8d3c-4a52-b8c4-5f1e0f0c6a01,
+withdrawal,2,1f2e3d4c-5b6a-4798-8a7b-6c5d4e3f2a04,1";
+        let outcomes = |transactions: &mut dyn Iterator<Item = _>| {
+            let mut trial_balance = TrialBalance::new();
+            let results: Vec<_> = transactions
+                .map(|tx: Result<_, DeserializationError>| {
+                    trial_balance.handle_transaction(tx.unwrap()).is_ok()
+                })
+                .collect();
+            (results, trial_balance.snapshot())
+        };
+        let expected = outcomes(&mut transactions(numeric.as_bytes()));
+        assert_eq!(expected.0, [true, false, true, true, false, true, true]);
+
+        let dialect = Dialect {
+            tx_ids: TxIds::Uuid,
+            ..Default::default()
+        };
+        let mut read = dialect.transactions(uuids.as_bytes());
+        let interner = read.interner().unwrap();
+        assert_eq!(outcomes(&mut read), expected);
+        // The unknown id of the dispute is interned as well
+        assert_eq!(interner.lock().unwrap().len(), 4);
+
+        let dialect = Dialect {
+            tx_ids: TxIds::String,
+            ..Default::default()
+        };
+        let strings = numeric.replace(",1,1", ",1,a").replace(",2,2", ",2,b");
+        assert_eq!(
+            outcomes(&mut dialect.transactions(strings.as_bytes())),
+            expected
+        );
+        assert!(Dialect::default()
+            .transactions(uuids.as_bytes())
+            .next()
+            .unwrap()
+            .is_err());
+    }
+}
@@ -4,4 +4,43 @@ use super::TransactionRow;
 pub enum DeserializationError {
     #[error("Could not further parse: {0:?}")]
     ParseError(TransactionRow),
+    #[error("Could not read row: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("{column} id {id} does not fit in the {width} bit ids of this build")]
+    IdOverflow {
+        column: &'static str,
+        id: u128,
+        width: u32,
+    },
+    #[error("Invalid UUID {0}")]
+    InvalidUuid(String),
+    #[error("More distinct transaction ids than fit in the ids of this build")]
+    TooManyIds,
+    #[error("Header misses column {0}")]
+    MissingColumn(String),
+    #[error("Line {line}, column {column}: {violation}")]
+    Strict {
+        line: u64,
+        column: usize,
+        violation: StrictViolation,
+    },
+}
+
+/// Represents a rule of strict reading that a line of the input breaks.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StrictViolation {
+    #[error("Header {0} is not type,client,tx,amount with an optional timestamp")]
+    Header(String),
+    #[error("Blank line")]
+    BlankLine,
+    #[error("Invalid UTF-8")]
+    Utf8,
+    #[error("Row has {found} columns where the header has {expected}")]
+    ColumnCount { expected: usize, found: usize },
+    #[error("Mutation carries an amount")]
+    AmountOnMutation,
+    #[error("Transfer is missing an amount")]
+    MissingAmount,
+    #[error("Malformed value: {0}")]
+    Malformed(String),
 }
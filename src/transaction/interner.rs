@@ -0,0 +1,198 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io::{Read, Write},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use serde::Deserialize;
+
+use super::{error::DeserializationError, RawTransactionId, TransactionId};
+
+/// Represents how the transaction ids of an input are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxIds {
+    /// Unsigned integers that fit the width of the build.
+    #[default]
+    Numeric,
+    /// Opaque strings, compared exactly.
+    String,
+    /// UUIDs with or without hyphens in any case, which are compared by value.
+    Uuid,
+}
+
+impl std::str::FromStr for TxIds {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "numeric" => Ok(TxIds::Numeric),
+            "string" => Ok(TxIds::String),
+            "uuid" => Ok(TxIds::Uuid),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TxIds {
+    /// Returns the form of the id that is interned, UUIDs are written lowercase with hyphens.
+    fn normalize<'a>(&self, name: &'a str) -> Result<Cow<'a, str>, DeserializationError> {
+        if *self != TxIds::Uuid {
+            return Ok(Cow::Borrowed(name));
+        }
+        let digits: Vec<u8> = name.bytes().filter(|&byte| byte != b'-').collect();
+        if digits.len() != 32 || !digits.iter().all(u8::is_ascii_hexdigit) {
+            return Err(DeserializationError::InvalidUuid(name.to_string()));
+        }
+        let mut uuid = String::with_capacity(36);
+        for (index, &digit) in digits.iter().enumerate() {
+            if matches!(index, 8 | 12 | 16 | 20) {
+                uuid.push('-');
+            }
+            uuid.push(char::from(digit.to_ascii_lowercase()));
+        }
+        // Hyphens are either left out or in their usual places
+        match name.len() {
+            32 => Ok(Cow::Owned(uuid)),
+            36 if uuid.eq_ignore_ascii_case(name) => Ok(Cow::Owned(uuid)),
+            _ => Err(DeserializationError::InvalidUuid(name.to_string())),
+        }
+    }
+}
+
+/// Represents the string ids of an input, each interned as a [`TransactionId`] in order of first appearance.
+///
+/// The ledger and everything else only see the numeric ids, so a repeated id is a duplicate and a mutation
+/// finds its transfer exactly like with numeric ids. Every name is stored once however often it is used.
+#[derive(Debug, Default)]
+pub struct Interner {
+    ids: HashMap<Arc<str>, TransactionId>,
+    names: Vec<Arc<str>>,
+}
+
+/// Represents an interner that is shared between the reader of an input and whoever writes its results.
+pub type SharedInterner = Arc<Mutex<Interner>>;
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the id of the name, interning it when it is new.
+    pub fn intern(
+        &mut self,
+        name: &str,
+        kind: TxIds,
+    ) -> Result<TransactionId, DeserializationError> {
+        let name = kind.normalize(name)?;
+        if let Some(&id) = self.ids.get(name.as_ref()) {
+            return Ok(id);
+        }
+        let id = RawTransactionId::try_from(self.names.len())
+            .map(TransactionId::new)
+            .map_err(|_| DeserializationError::TooManyIds)?;
+        let name: Arc<str> = Arc::from(name);
+        self.ids.insert(name.clone(), id);
+        self.names.push(name);
+        Ok(id)
+    }
+
+    /// Returns the name the id was interned for.
+    pub fn resolve(&self, id: TransactionId) -> Option<&str> {
+        self.names.get(id.id() as usize).map(AsRef::as_ref)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Copies csv as written by the engine, like a statement or screening report, with the ids
+    /// of its `tx` column replaced by their names. Ids that were not interned are kept.
+    pub fn resolve_csv<R, W>(&self, reader: R, w: &mut W) -> Result<(), csv::Error>
+    where
+        R: Read,
+        W: Write,
+    {
+        let mut rdr = csv::Reader::from_reader(reader);
+        let mut wtr = csv::Writer::from_writer(w);
+        let headers = rdr.headers()?.clone();
+        wtr.write_record(&headers)?;
+        let column = headers.iter().position(|header| header == "tx");
+        for record in rdr.records() {
+            let record = record?;
+            let name = column
+                .and_then(|column| record[column].parse().ok())
+                .and_then(|id| self.resolve(TransactionId::new(id)));
+            match (column, name) {
+                (Some(column), Some(name)) => {
+                    wtr.write_record(record.iter().enumerate().map(|(index, field)| {
+                        if index == column {
+                            name
+                        } else {
+                            field
+                        }
+                    }))?
+                }
+                _ => wtr.write_record(&record)?,
+            }
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// Locks the shared interner, which stays usable when a holder panicked.
+pub fn lock(interner: &SharedInterner) -> std::sync::MutexGuard<'_, Interner> {
+    interner.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interner, TxIds};
+    use crate::transaction::{error::DeserializationError, TransactionId};
+
+    #[test]
+    fn test_interner() {
+        let mut interner = Interner::new();
+        let a = interner.intern("a-1", TxIds::String).unwrap();
+        let b = interner.intern("B-1", TxIds::String).unwrap();
+        assert_eq!(interner.intern("a-1", TxIds::String).unwrap(), a);
+        assert_ne!(interner.intern("b-1", TxIds::String).unwrap(), b);
+        assert_eq!(interner.resolve(b), Some("B-1"));
+        assert_eq!(interner.resolve(TransactionId::new(10)), None);
+
+        let uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let id = interner.intern(uuid, TxIds::Uuid).unwrap();
+        for same in [
+            "67E55044-10B1-426F-9247-BB680E5FE0C8",
+            "67e5504410b1426f9247bb680e5fe0c8",
+        ] {
+            assert_eq!(interner.intern(same, TxIds::Uuid).unwrap(), id);
+        }
+        for invalid in [
+            "67e55044-10b1-426f-9247",
+            "67e55044-10b1-426f-9247-bb680e5fe0cg",
+            "67e5504410b1-426f-9247-bb680e5f-e0c8",
+        ] {
+            assert!(matches!(
+                interner.intern(invalid, TxIds::Uuid),
+                Err(DeserializationError::InvalidUuid(_))
+            ));
+        }
+        assert_eq!(interner.len(), 4);
+
+        let mut resolved = Vec::new();
+        interner
+            .resolve_csv("seq,tx\n1,0\n2,3\n3,9\n".as_bytes(), &mut resolved)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(resolved).unwrap(),
+            format!("seq,tx\n1,a-1\n2,{uuid}\n3,9\n")
+        );
+    }
+}
@@ -3,36 +3,66 @@ use serde::{Deserialize, Serialize};
 
 pub use transaction::*;
 
-use crate::client::Client;
+use error::DeserializationError;
+#[allow(clippy::module_inception)]
 mod transaction;
 
+#[cfg(feature = "async")]
+pub mod async_reader;
 pub mod charge_back;
 pub mod deposit;
+pub mod dialect;
 pub mod dispute;
 pub mod error;
+pub mod finalize;
+pub mod interner;
+pub mod pipeline;
+pub mod reject;
+pub mod release;
 pub mod resolve;
+mod splitter;
+pub mod strict;
+pub mod validate;
 pub mod withdrawal;
 
+/// Returns the csv configuration that is used for reading transactions.
+fn reader_builder() -> csv::ReaderBuilder {
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .flexible(true)
+        .has_headers(true)
+        .trim(csv::Trim::All);
+    builder
+}
+
 pub fn transaction_reader<R>(reader: R) -> csv::DeserializeRecordsIntoIter<R, TransactionRow>
 where
     R: std::io::Read,
 {
-    let rdr = csv::ReaderBuilder::new()
-        .flexible(true)
-        .has_headers(true)
-        .trim(csv::Trim::All)
-        .from_reader(reader);
+    let rdr = reader_builder().from_reader(reader);
     rdr.into_deserialize()
 }
 
+/// Reads the rows like [`transaction_reader`] and converts each of them into a [`Transaction`].
+pub fn transactions<R>(reader: R) -> impl Iterator<Item = Result<Transaction, DeserializationError>>
+where
+    R: std::io::Read,
+{
+    transaction_reader(reader).map(|tx_row| Transaction::try_from(tx_row?))
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct TransactionRow {
     #[serde(rename = "type")]
     transaction_type: TransactionType,
-    client: Client,
+    // Ids are read wider than any configured width so one that does not fit is reported as such
+    client: u128,
     #[serde(rename = "tx")]
-    transaction_id: TransactionId,
+    transaction_id: u128,
     amount: Option<Decimal>,
+    /// Optional moment the transaction took place. Older inputs do not carry this column.
+    #[serde(default)]
+    timestamp: Option<Timestamp>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
@@ -47,14 +77,69 @@ pub enum TransactionType {
     Resolve,
     #[serde(rename = "chargeback")]
     ChargeBack,
+    #[serde(rename = "finalize")]
+    Finalize,
+    #[serde(rename = "release")]
+    Release,
+    #[serde(rename = "reject")]
+    Reject,
+}
+
+impl TransactionType {
+    /// Returns the name of the type as it is written in the input.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::ChargeBack => "chargeback",
+            TransactionType::Finalize => "finalize",
+            TransactionType::Release => "release",
+            TransactionType::Reject => "reject",
+        }
+    }
 }
 
+/// Integer a transaction ID is stored as, a `u32` unless the `wide-ids` feature widens it to a `u64`.
+#[cfg(not(feature = "wide-ids"))]
+pub type RawTransactionId = u32;
+#[cfg(feature = "wide-ids")]
+pub type RawTransactionId = u64;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, Hash)]
+pub struct TransactionId(RawTransactionId);
+
+/// Represents the moment a transaction took place in seconds since the Unix epoch.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, Hash)]
-pub struct TransactionId(u32);
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub fn from_secs(secs: u64) -> Self {
+        Self(secs)
+    }
+
+    pub fn as_secs(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the number of seconds between `earlier` and `self`, or zero if `earlier` is later.
+    pub fn seconds_since(&self, earlier: Timestamp) -> u64 {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// Returns the timestamp `secs` seconds after `self`.
+    pub fn add_secs(&self, secs: u64) -> Self {
+        Self(self.0.saturating_add(secs))
+    }
+}
 
-#[cfg(test)]
 impl TransactionId {
-    pub fn new(id: u32) -> Self {
+    pub fn new(id: RawTransactionId) -> Self {
         Self(id)
     }
+
+    pub fn id(&self) -> RawTransactionId {
+        self.0
+    }
 }
@@ -0,0 +1,229 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use super::{
+    error::DeserializationError,
+    splitter::{self, Splitter},
+    Transaction,
+};
+
+// Number of bytes that are parsed by a worker at once.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+type Parsed = Result<Transaction, DeserializationError>;
+
+/// Represents the transactions of an input that are parsed on a pool of worker threads.
+///
+/// A reader thread splits the input into chunks on record boundaries, the workers parse and convert
+/// the records, and the iterator yields the transactions in their original order.
+/// The number of chunks in flight is bounded, so a slow consumer stops the reader.
+pub struct ParallelTransactions {
+    results: Receiver<(usize, Vec<Parsed>)>,
+    // Chunks that arrived before the chunk that is next in line
+    pending: BTreeMap<usize, Vec<Parsed>>,
+    next: usize,
+    current: std::vec::IntoIter<Parsed>,
+    // Every consumed chunk allows the reader to read another one
+    credits: SyncSender<()>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// Reads the transactions like [`transactions`](super::transactions), parsing them on `workers` threads.
+pub fn parallel_transactions<R>(reader: R, workers: usize) -> ParallelTransactions
+where
+    R: Read + Send + 'static,
+{
+    let workers = workers.max(1);
+    let in_flight = workers * 4;
+
+    let (credits, credit_receiver) = sync_channel::<()>(in_flight);
+    for _ in 0..in_flight {
+        credits.send(()).expect("Credits fit the channel");
+    }
+    let (chunk_sender, chunk_receiver) = sync_channel::<(usize, Vec<u8>, csv::Position)>(in_flight);
+    let (result_sender, results) = sync_channel(in_flight);
+    let (header_sender, header_receiver) = sync_channel::<csv::ByteRecord>(0);
+
+    let reader_results = result_sender.clone();
+    let mut threads = Vec::with_capacity(workers + 1);
+    threads.push(thread::spawn(move || {
+        let mut reader = reader;
+        let mut splitter = Splitter::new();
+        // The first record holds the headers
+        let headers = match next_chunk(&mut splitter, &mut reader, 1) {
+            Ok(Some((data, _))) => splitter::headers(data),
+            _ => csv::ByteRecord::new(),
+        };
+        drop(header_sender.send(headers));
+
+        let mut index = 0;
+        while credit_receiver.recv().is_ok() {
+            let (data, position) = match next_chunk(&mut splitter, &mut reader, u64::MAX) {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => return,
+                Err(err) => {
+                    // The error takes the place of the chunk that could not be read
+                    let error = DeserializationError::Csv(csv::Error::from(err));
+                    let _ = reader_results.send((index, vec![Err(error)]));
+                    return;
+                }
+            };
+            if chunk_sender.send((index, data, position)).is_err() {
+                return;
+            }
+            index += 1;
+        }
+    }));
+
+    let headers = Arc::new(header_receiver.recv().unwrap_or_default());
+    let chunk_receiver = Arc::new(Mutex::new(chunk_receiver));
+    for _ in 0..workers {
+        let chunk_receiver = Arc::clone(&chunk_receiver);
+        let result_sender = result_sender.clone();
+        let headers = Arc::clone(&headers);
+        threads.push(thread::spawn(move || loop {
+            let received = chunk_receiver.lock().expect("Worker panicked").recv();
+            let Ok((index, data, position)) = received else {
+                return;
+            };
+            let parsed = splitter::parse(data, position, &headers)
+                .into_iter()
+                .map(|tx_row| Transaction::try_from(tx_row?))
+                .collect();
+            if result_sender.send((index, parsed)).is_err() {
+                return;
+            }
+        }));
+    }
+
+    ParallelTransactions {
+        results,
+        pending: BTreeMap::new(),
+        next: 0,
+        current: Vec::new().into_iter(),
+        credits,
+        threads,
+    }
+}
+
+/// Reads the input until the splitter hands out the next chunk.
+fn next_chunk<R: Read>(
+    splitter: &mut Splitter,
+    reader: &mut R,
+    max_records: u64,
+) -> io::Result<Option<(Vec<u8>, csv::Position)>> {
+    loop {
+        if let Some(chunk) = splitter.split(max_records, CHUNK_SIZE) {
+            return Ok(Some(chunk));
+        }
+        if splitter.done() {
+            return Ok(None);
+        }
+        splitter.fill(reader)?;
+    }
+}
+
+impl Iterator for ParallelTransactions {
+    type Item = Parsed;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(tx) = self.current.next() {
+                return Some(tx);
+            }
+
+            let chunk = match self.pending.remove(&self.next) {
+                Some(chunk) => chunk,
+                None => match self.results.recv() {
+                    Ok((index, chunk)) if index == self.next => chunk,
+                    Ok((index, chunk)) => {
+                        self.pending.insert(index, chunk);
+                        continue;
+                    }
+                    // All chunks have been consumed
+                    Err(_) => return None,
+                },
+            };
+            self.next += 1;
+            self.current = chunk.into_iter();
+            // The reader is done once the consumer stops handing out credits
+            let _ = self.credits.try_send(());
+        }
+    }
+}
+
+impl Drop for ParallelTransactions {
+    fn drop(&mut self) {
+        // Stop the reader before waiting for the threads
+        let (credits, _) = sync_channel(0);
+        drop(std::mem::replace(&mut self.credits, credits));
+        let (_, results) = sync_channel(0);
+        drop(std::mem::replace(&mut self.results, results));
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parallel_transactions;
+    use crate::transaction::transactions;
+
+    #[test]
+    fn test_parallel_transactions_in_order() {
+        let mut data = "type, client, tx, amount\n".to_string();
+        for i in 0..50_000u32 {
+            match i % 6 {
+                0 => data.push_str(&format!("deposit, {}, {i}, {}.1234\n", i % 13, i % 97)),
+                1 => data.push_str(&format!("withdrawal, {}, {i}, 1.5\r\n", i % 13)),
+                2 => data.push_str(&format!("dispute, {}, {}\n\n", i % 13, i - 2)),
+                // Missing amount and invalid type
+                3 => data.push_str(&format!("deposit, {}, {i},\n", i % 13)),
+                4 => data.push_str(&format!("\"deposit\", \"{}\", {i}, \"1.0\"\n", i % 13)),
+                _ => data.push_str(&format!("transfer, {}, {i}, 1.0\n", i % 13)),
+            }
+        }
+        // Without a trailing line feed
+        data.push_str("deposit, 1, 1000000, 1.0");
+
+        let expected: Vec<String> = transactions(std::io::Cursor::new(data.clone()))
+            .map(|tx| format!("{tx:?}"))
+            .collect();
+        for workers in [1, 4] {
+            let parsed: Vec<String> =
+                parallel_transactions(std::io::Cursor::new(data.clone()), workers)
+                    .map(|tx| format!("{tx:?}"))
+                    .collect();
+            assert_eq!(parsed, expected);
+        }
+    }
+
+    #[test]
+    fn test_parallel_transactions_dropped_early() {
+        let data =
+            "type, client, tx, amount\n".to_string() + &"deposit, 1, 1, 1.0\n".repeat(100_000);
+        let mut parsed = parallel_transactions(std::io::Cursor::new(data), 2);
+        assert!(parsed.next().unwrap().is_ok());
+        drop(parsed);
+    }
+
+    #[test]
+    fn test_parallel_transactions_empty() {
+        assert_eq!(
+            parallel_transactions(std::io::Cursor::new(""), 2).count(),
+            0
+        );
+        assert_eq!(
+            parallel_transactions(std::io::Cursor::new("type, client, tx, amount\n"), 2).count(),
+            0
+        );
+    }
+}
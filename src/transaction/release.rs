@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Timestamp, TransactionId};
+use crate::client::Client;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub struct Release {
+    client: Client,
+    tx: TransactionId,
+    timestamp: Option<Timestamp>,
+}
+
+impl Release {
+    pub fn new(client: Client, tx: TransactionId) -> Self {
+        Self {
+            client,
+            tx,
+            timestamp: None,
+        }
+    }
+    pub fn client(&self) -> Client {
+        self.client
+    }
+    pub fn transaction_id(&self) -> TransactionId {
+        self.tx
+    }
+    pub fn with_timestamp(mut self, timestamp: Option<Timestamp>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+}
@@ -1,17 +1,22 @@
 use serde::{Deserialize, Serialize};
 
-use super::TransactionId;
+use super::{Timestamp, TransactionId};
 use crate::client::Client;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct Resolve {
     client: Client,
     tx: TransactionId,
+    timestamp: Option<Timestamp>,
 }
 
 impl Resolve {
     pub fn new(client: Client, tx: TransactionId) -> Self {
-        Self { client, tx }
+        Self {
+            client,
+            tx,
+            timestamp: None,
+        }
     }
     pub fn client(&self) -> Client {
         self.client
@@ -19,4 +24,35 @@ impl Resolve {
     pub fn transaction_id(&self) -> TransactionId {
         self.tx
     }
+    pub fn with_timestamp(mut self, timestamp: Option<Timestamp>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+}
+
+/// Writes the resolves as rows of the input format, so generated resolves can be kept with the input.
+pub fn to_csv<'a, I, W>(resolves: I, w: &mut W) -> Result<(), csv::Error>
+where
+    I: IntoIterator<Item = &'a Resolve>,
+    W: std::io::Write,
+{
+    let mut wtr = csv::Writer::from_writer(w);
+    wtr.write_record(["type", "client", "tx", "amount", "timestamp"])?;
+    for resolve in resolves {
+        wtr.write_record([
+            "resolve".to_string(),
+            resolve.client.id().to_string(),
+            resolve.tx.id().to_string(),
+            String::new(),
+            resolve
+                .timestamp
+                .map(|timestamp| timestamp.as_secs().to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+    wtr.flush()?;
+    Ok(())
 }
@@ -0,0 +1,167 @@
+use std::io::{self, Cursor, Read};
+
+use super::{reader_builder, TransactionRow};
+
+// Number of bytes that are requested from the input at once.
+const READ_SIZE: usize = 64 * 1024;
+
+/// Splits an input into chunks that end on a record boundary.
+///
+/// A line feed outside of a quoted field always ends a record. The position of every chunk is tracked
+/// the way the csv reader counts them, skipping empty lines, so that each chunk can be parsed on its own.
+pub(super) struct Splitter {
+    buffer: Vec<u8>,
+    // Bytes of the buffer that have been scanned and the quote state at that point
+    scanned: usize,
+    quoted: bool,
+    line_empty: bool,
+    lines: u64,
+    // End of the last complete record in the buffer and the lines and records up to there
+    boundary: usize,
+    boundary_lines: u64,
+    boundary_records: u64,
+    // Position of the first byte of the buffer in the input
+    position: csv::Position,
+    done: bool,
+}
+
+impl Splitter {
+    pub(super) fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            scanned: 0,
+            quoted: false,
+            line_empty: true,
+            lines: 0,
+            boundary: 0,
+            boundary_lines: 0,
+            boundary_records: 0,
+            position: csv::Position::new(),
+            done: false,
+        }
+    }
+
+    /// Returns whether the entire input has been read.
+    pub(super) fn done(&self) -> bool {
+        self.done
+    }
+
+    /// Reads more of the input into the buffer.
+    pub(super) fn fill<R: Read>(&mut self, reader: &mut R) -> io::Result<()> {
+        let len = self.buffer.len();
+        self.buffer.resize(len + READ_SIZE, 0);
+        let read = loop {
+            match reader.read(&mut self.buffer[len..]) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                read => break read,
+            }
+        };
+        self.filled(len, read)
+    }
+
+    /// Reads more of the input into the buffer without blocking.
+    #[cfg(feature = "async")]
+    pub(super) async fn fill_async<R>(&mut self, reader: &mut R) -> io::Result<()>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        use tokio::io::AsyncReadExt;
+
+        let len = self.buffer.len();
+        self.buffer.resize(len + READ_SIZE, 0);
+        let read = reader.read(&mut self.buffer[len..]).await;
+        self.filled(len, read)
+    }
+
+    fn filled(&mut self, len: usize, read: io::Result<usize>) -> io::Result<()> {
+        let read = read.inspect_err(|_| self.buffer.truncate(len))?;
+        self.buffer.truncate(len + read);
+        self.done = read == 0;
+        Ok(())
+    }
+
+    /// Splits off a chunk of at most `max_records` records together with the position it starts at.
+    ///
+    /// A chunk is only split off once it holds `max_records` records or `min_bytes` bytes, or once the input is done.
+    /// Returns `None` when more input is needed, or when the input is done and everything has been split off.
+    pub(super) fn split(
+        &mut self,
+        max_records: u64,
+        min_bytes: usize,
+    ) -> Option<(Vec<u8>, csv::Position)> {
+        while self.scanned < self.buffer.len() && self.boundary_records < max_records {
+            let byte = self.buffer[self.scanned];
+            self.scanned += 1;
+            match byte {
+                b'"' => {
+                    self.quoted = !self.quoted;
+                    self.line_empty = false;
+                }
+                b'\n' if !self.quoted => {
+                    self.lines += 1;
+                    if !self.line_empty {
+                        self.boundary_records += 1;
+                    }
+                    self.boundary = self.scanned;
+                    self.boundary_lines = self.lines;
+                    self.line_empty = true;
+                }
+                b'\n' => self.lines += 1,
+                b'\r' => {}
+                _ => self.line_empty = false,
+            }
+        }
+
+        if self.done && self.scanned == self.buffer.len() && self.boundary < self.buffer.len() {
+            // The last record is not followed by a line feed
+            if !self.line_empty {
+                self.boundary_records += 1;
+            }
+            self.boundary = self.buffer.len();
+            self.boundary_lines = self.lines;
+            self.line_empty = true;
+        }
+
+        let full = self.boundary_records >= max_records || self.boundary >= min_bytes;
+        if self.boundary == 0 || !(full || self.done) {
+            return None;
+        }
+
+        let rest = self.buffer.split_off(self.boundary);
+        let data = std::mem::replace(&mut self.buffer, rest);
+        let start = self.position.clone();
+        self.position.set_byte(start.byte() + data.len() as u64);
+        self.position.set_line(start.line() + self.boundary_lines);
+        self.position
+            .set_record(start.record() + self.boundary_records);
+        self.scanned -= self.boundary;
+        self.lines -= self.boundary_lines;
+        self.boundary = 0;
+        self.boundary_lines = 0;
+        self.boundary_records = 0;
+        Some((data, start))
+    }
+}
+
+/// Reads the headers from the chunk that holds the first record.
+pub(super) fn headers(data: Vec<u8>) -> csv::ByteRecord {
+    let mut rdr = reader_builder().from_reader(Cursor::new(data));
+    rdr.byte_headers().cloned().unwrap_or_default()
+}
+
+/// Parses the rows of a chunk, reporting positions relative to the entire input.
+pub(super) fn parse(
+    data: Vec<u8>,
+    position: csv::Position,
+    headers: &csv::ByteRecord,
+) -> Vec<Result<TransactionRow, csv::Error>> {
+    let mut rdr = reader_builder()
+        .has_headers(false)
+        .from_reader(Cursor::new(data));
+    if let Err(err) = rdr.seek_raw(io::SeekFrom::Start(0), position) {
+        return vec![Err(err)];
+    }
+    rdr.into_byte_records()
+        .map(|record| record?.deserialize(Some(headers)))
+        .collect()
+}
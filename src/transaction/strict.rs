@@ -0,0 +1,276 @@
+use std::io::{BufRead, BufReader, Read};
+
+use csv::StringRecord;
+
+use super::{
+    error::{DeserializationError, StrictViolation},
+    Transaction, TransactionRow, TransactionType,
+};
+
+// Columns every strict input starts with, optionally followed by `timestamp`.
+const COLUMNS: [&str; 4] = ["type", "client", "tx", "amount"];
+const TIMESTAMP: &str = "timestamp";
+// Position of the amount column, counting from one.
+const AMOUNT_COLUMN: usize = 4;
+
+/// Represents the transactions of an input that is read strictly.
+///
+/// Unlike [`transactions`](super::transactions), which is lenient towards ragged rows and ignores an amount on mutations,
+/// every row must have the columns of the header, which must be exactly `type,client,tx,amount` with an optional
+/// `timestamp`. Blank lines, invalid UTF-8 and amounts on mutation rows are rejected.
+/// Every row must be on a single line, so quoted fields can not contain line breaks.
+///
+/// Each violation is reported with its line and column, counting from one.
+/// A row with a violation is skipped, an invalid header ends the input.
+pub struct StrictTransactions<R> {
+    reader: R,
+    line: u64,
+    buf: Vec<u8>,
+    headers: Option<StringRecord>,
+    done: bool,
+}
+
+/// Reads the transactions of the input strictly, see [`StrictTransactions`].
+pub fn strict_transactions<R>(reader: R) -> StrictTransactions<BufReader<R>>
+where
+    R: Read,
+{
+    StrictTransactions {
+        reader: BufReader::new(reader),
+        line: 0,
+        buf: Vec::new(),
+        headers: None,
+        done: false,
+    }
+}
+
+impl<R: BufRead> StrictTransactions<R> {
+    /// Reads the next line into the buffer without its line ending, returns `false` at the end of the input.
+    fn read_line(&mut self) -> Result<bool, DeserializationError> {
+        self.buf.clear();
+        if self
+            .reader
+            .read_until(b'\n', &mut self.buf)
+            .map_err(csv::Error::from)?
+            == 0
+        {
+            return Ok(false);
+        }
+        self.line += 1;
+        if self.buf.ends_with(b"\n") {
+            self.buf.pop();
+            if self.buf.ends_with(b"\r") {
+                self.buf.pop();
+            }
+        }
+        Ok(true)
+    }
+
+    fn violation(&self, column: usize, violation: StrictViolation) -> DeserializationError {
+        DeserializationError::Strict {
+            line: self.line,
+            column,
+            violation,
+        }
+    }
+
+    /// Splits the current line into its fields.
+    fn record(&self) -> Result<StringRecord, DeserializationError> {
+        if self.buf.iter().all(u8::is_ascii_whitespace) {
+            return Err(self.violation(1, StrictViolation::BlankLine));
+        }
+        if let Err(err) = std::str::from_utf8(&self.buf) {
+            let column = 1 + self.buf[..err.valid_up_to()]
+                .iter()
+                .filter(|&&byte| byte == b',')
+                .count();
+            return Err(self.violation(column, StrictViolation::Utf8));
+        }
+        let mut record = StringRecord::new();
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(self.buf.as_slice())
+            .read_record(&mut record)
+            .map_err(|err| self.violation(1, StrictViolation::Malformed(err.to_string())))?;
+        Ok(record)
+    }
+
+    fn header(&mut self) -> Result<StringRecord, DeserializationError> {
+        if !self.read_line()? {
+            // An empty input misses the header on its first line
+            self.line = 1;
+            return Err(self.violation(1, StrictViolation::BlankLine));
+        }
+        let headers = self.record()?;
+        let expected = COLUMNS
+            .iter()
+            .chain((headers.len() > COLUMNS.len()).then_some(&TIMESTAMP));
+        let found: Vec<&str> = headers.iter().collect();
+        if let Some(column) = expected
+            .zip(&found)
+            .position(|(expected, found)| expected != found)
+            .or_else(|| (!(4..=5).contains(&found.len())).then_some(found.len().min(5)))
+        {
+            return Err(self.violation(column + 1, StrictViolation::Header(found.join(","))));
+        }
+        Ok(headers)
+    }
+
+    fn row(&self, headers: &StringRecord) -> Result<Transaction, DeserializationError> {
+        let record = self.record()?;
+        if record.len() != headers.len() {
+            return Err(self.violation(
+                record.len().min(headers.len()) + 1,
+                StrictViolation::ColumnCount {
+                    expected: headers.len(),
+                    found: record.len(),
+                },
+            ));
+        }
+        let row: TransactionRow = record.deserialize(Some(headers)).map_err(|err| {
+            // The position of the error is that of the single line record, only the field is of interest
+            match err.kind() {
+                csv::ErrorKind::Deserialize { err, .. } => self.violation(
+                    err.field().map_or(1, |field| field as usize + 1),
+                    StrictViolation::Malformed(err.kind().to_string()),
+                ),
+                _ => self.violation(1, StrictViolation::Malformed(err.to_string())),
+            }
+        })?;
+        let transfer = matches!(
+            row.transaction_type,
+            TransactionType::Deposit | TransactionType::Withdrawal
+        );
+        match (transfer, row.amount) {
+            (true, None) => Err(self.violation(AMOUNT_COLUMN, StrictViolation::MissingAmount)),
+            (false, Some(_)) => {
+                Err(self.violation(AMOUNT_COLUMN, StrictViolation::AmountOnMutation))
+            }
+            _ => Transaction::try_from(row),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for StrictTransactions<R> {
+    type Item = Result<Transaction, DeserializationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let headers = match self.headers.take() {
+            Some(headers) => headers,
+            None => match self.header() {
+                Ok(headers) => headers,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            },
+        };
+        let res = match self.read_line() {
+            Ok(true) => Some(self.row(&headers)),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(err) => Some(Err(err)),
+        };
+        self.headers = Some(headers);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strict_transactions;
+    use crate::transaction::{
+        error::{DeserializationError, StrictViolation},
+        transactions,
+    };
+
+    /// Returns the line, column and violation of every row that is rejected.
+    fn violations(data: &[u8]) -> Vec<(u64, usize, StrictViolation)> {
+        strict_transactions(data)
+            .filter_map(|tx| match tx {
+                Err(DeserializationError::Strict {
+                    line,
+                    column,
+                    violation,
+                }) => Some((line, column, violation)),
+                Err(err) => panic!("Unexpected error {err}"),
+                Ok(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_strict_transactions() {
+        let data = "type, client, tx, amount
+deposit, 1, 1, 1.0
+deposit, 2, 2
+dispute, 2, 2,
+
+dispute, 2, 2, 5
+withdrawal, 1, 3, 0.5, 100
+resolve, 2, 2,\r
+chargeback, two, 2,
+deposit, 1, 4,
+withdrawal, 1, 5,\"0.5\"";
+        let lenient: Vec<_> = transactions(data.as_bytes()).collect();
+        let strict: Vec<_> = strict_transactions(data.as_bytes()).collect();
+        assert_eq!(lenient.iter().filter(|tx| tx.is_ok()).count(), 6);
+        assert_eq!(strict.iter().filter(|tx| tx.is_ok()).count(), 4);
+
+        let found = violations(data.as_bytes());
+        assert_eq!(found.len(), 6);
+        assert_eq!(
+            found[0],
+            (
+                3,
+                4,
+                StrictViolation::ColumnCount {
+                    expected: 4,
+                    found: 3
+                }
+            )
+        );
+        assert_eq!(found[1], (5, 1, StrictViolation::BlankLine));
+        assert_eq!(found[2], (6, 4, StrictViolation::AmountOnMutation));
+        assert_eq!(found[3].0, 7);
+        assert!(matches!(found[4], (9, 2, StrictViolation::Malformed(_))));
+        assert_eq!(found[5], (10, 4, StrictViolation::MissingAmount));
+
+        let mut invalid = b"type,client,tx,amount\ndeposit,1,1,1\ndeposit,1,2,".to_vec();
+        invalid.extend_from_slice(&[0xff, b'\n']);
+        assert!(matches!(
+            violations(&invalid)[..],
+            [(3, 4, StrictViolation::Utf8)]
+        ));
+    }
+
+    #[test]
+    fn test_strict_header() {
+        let header = |data: &str| violations(data.as_bytes());
+        assert_eq!(header("type,client,tx,amount,timestamp\n"), []);
+        assert_eq!(header("type,client,tx,amount\ndeposit,1,1,1\n"), []);
+        assert!(matches!(
+            header("type,tx,client,amount\ndeposit,1,1,1\n")[..],
+            [(1, 2, StrictViolation::Header(_))]
+        ));
+        assert!(matches!(
+            header("type,client,tx\n")[..],
+            [(1, 4, StrictViolation::Header(_))]
+        ));
+        assert!(matches!(
+            header("type,client,tx,amount,timestamp,note\n")[..],
+            [(1, 6, StrictViolation::Header(_))]
+        ));
+        assert!(matches!(
+            header("")[..],
+            [(1, 1, StrictViolation::BlankLine)]
+        ));
+    }
+}
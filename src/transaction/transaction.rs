@@ -5,7 +5,8 @@ use crate::client::Client;
 
 use super::{
     charge_back::ChargeBack, deposit::Deposit, dispute::Dispute, error::DeserializationError,
-    resolve::Resolve, withdrawal::Withdrawal, TransactionId, TransactionRow, TransactionType,
+    finalize::Finalize, reject::Reject, release::Release, resolve::Resolve, withdrawal::Withdrawal,
+    Timestamp, TransactionId, TransactionRow, TransactionType,
 };
 
 /// Represents all possible transactions
@@ -22,11 +23,34 @@ impl Transaction {
         match self {
             Transaction::Transfer(Transfer::Deposit(d)) => d.client(),
             Transaction::Transfer(Transfer::Withdrawal(w)) => w.client(),
-            Transaction::Mutation(m) => match m {
-                Mutation::Dispute(d) => d.client(),
-                Mutation::Resolve(r) => r.client(),
-                Mutation::ChargeBack(c) => c.client(),
-            },
+            Transaction::Mutation(m) => m.client(),
+        }
+    }
+
+    pub fn transaction_id(&self) -> TransactionId {
+        match self {
+            Transaction::Transfer(t) => t.transaction_id(),
+            Transaction::Mutation(m) => m.transaction_id(),
+        }
+    }
+
+    pub fn transaction_type(&self) -> TransactionType {
+        match self {
+            Transaction::Transfer(Transfer::Deposit(_)) => TransactionType::Deposit,
+            Transaction::Transfer(Transfer::Withdrawal(_)) => TransactionType::Withdrawal,
+            Transaction::Mutation(Mutation::Dispute(_)) => TransactionType::Dispute,
+            Transaction::Mutation(Mutation::Resolve(_)) => TransactionType::Resolve,
+            Transaction::Mutation(Mutation::ChargeBack(_)) => TransactionType::ChargeBack,
+            Transaction::Mutation(Mutation::Finalize(_)) => TransactionType::Finalize,
+            Transaction::Mutation(Mutation::Release(_)) => TransactionType::Release,
+            Transaction::Mutation(Mutation::Reject(_)) => TransactionType::Reject,
+        }
+    }
+
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Transaction::Transfer(t) => t.timestamp(),
+            Transaction::Mutation(m) => m.timestamp(),
         }
     }
 }
@@ -35,27 +59,51 @@ impl TryFrom<TransactionRow> for Transaction {
     type Error = DeserializationError;
 
     fn try_from(value: TransactionRow) -> Result<Self, Self::Error> {
+        let client = Client::new(narrow(value.client, "client")?);
+        let id = TransactionId::new(narrow(value.transaction_id, "tx")?);
+        let timestamp = value.timestamp;
         match (value.transaction_type, value.amount) {
             (TransactionType::Deposit, Some(amount)) => Ok(Transaction::Transfer(
-                Transfer::Deposit(Deposit::new(value.client, value.transaction_id, amount)),
+                Transfer::Deposit(Deposit::new(client, id, amount).with_timestamp(timestamp)),
             )),
             (TransactionType::Withdrawal, Some(amount)) => Ok(Transaction::Transfer(
-                Transfer::Withdrawal(Withdrawal::new(value.client, value.transaction_id, amount)),
+                Transfer::Withdrawal(Withdrawal::new(client, id, amount).with_timestamp(timestamp)),
             )),
             (TransactionType::Dispute, _) => Ok(Transaction::Mutation(Mutation::Dispute(
-                Dispute::new(value.client, value.transaction_id),
+                Dispute::new(client, id).with_timestamp(timestamp),
             ))),
             (TransactionType::Resolve, _) => Ok(Transaction::Mutation(Mutation::Resolve(
-                Resolve::new(value.client, value.transaction_id),
+                Resolve::new(client, id).with_timestamp(timestamp),
             ))),
             (TransactionType::ChargeBack, _) => Ok(Transaction::Mutation(Mutation::ChargeBack(
-                ChargeBack::new(value.client, value.transaction_id),
+                ChargeBack::new(client, id).with_timestamp(timestamp),
+            ))),
+            (TransactionType::Finalize, _) => Ok(Transaction::Mutation(Mutation::Finalize(
+                Finalize::new(client, id).with_timestamp(timestamp),
+            ))),
+            (TransactionType::Release, _) => Ok(Transaction::Mutation(Mutation::Release(
+                Release::new(client, id).with_timestamp(timestamp),
+            ))),
+            (TransactionType::Reject, _) => Ok(Transaction::Mutation(Mutation::Reject(
+                Reject::new(client, id).with_timestamp(timestamp),
             ))),
             _ => Err(DeserializationError::ParseError(value)),
         }
     }
 }
 
+/// Converts an id as read into the configured width.
+fn narrow<T>(id: u128, column: &'static str) -> Result<T, DeserializationError>
+where
+    T: TryFrom<u128>,
+{
+    T::try_from(id).map_err(|_| DeserializationError::IdOverflow {
+        column,
+        id,
+        width: 8 * std::mem::size_of::<T>() as u32,
+    })
+}
+
 /// Represents a category FIAT moving in or out of an account.
 ///
 /// These transactions can be mutated by a [`Mutation`] transaction after being processed.
@@ -66,6 +114,13 @@ pub enum Transfer {
 }
 
 impl Transfer {
+    pub fn client(&self) -> Client {
+        match self {
+            Transfer::Deposit(d) => d.client(),
+            Transfer::Withdrawal(w) => w.client(),
+        }
+    }
+
     pub fn transaction_id(&self) -> TransactionId {
         match self {
             Transfer::Deposit(d) => d.transaction_id(),
@@ -79,6 +134,13 @@ impl Transfer {
             Transfer::Withdrawal(w) => w.amount(),
         }
     }
+
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Transfer::Deposit(d) => d.timestamp(),
+            Transfer::Withdrawal(w) => w.timestamp(),
+        }
+    }
 }
 
 /// Mutations represent transactions that are dependent on a [`Transfer`] transaction.
@@ -88,21 +150,55 @@ pub enum Mutation {
     Dispute(Dispute),
     Resolve(Resolve),
     ChargeBack(ChargeBack),
+    /// Marks a transaction as settled so it can no longer be mutated and its record can be evicted.
+    Finalize(Finalize),
+    /// Credits the pending funds of a deposit that was held for review.
+    Release(Release),
+    /// Discards the pending funds of a deposit that was held for review.
+    Reject(Reject),
 }
 
 impl Mutation {
+    pub fn client(&self) -> Client {
+        match self {
+            Mutation::Dispute(d) => d.client(),
+            Mutation::Resolve(r) => r.client(),
+            Mutation::ChargeBack(c) => c.client(),
+            Mutation::Finalize(f) => f.client(),
+            Mutation::Release(r) => r.client(),
+            Mutation::Reject(r) => r.client(),
+        }
+    }
+
     pub fn transaction_id(&self) -> TransactionId {
         match self {
             Mutation::Dispute(d) => d.transaction_id(),
             Mutation::Resolve(r) => r.transaction_id(),
             Mutation::ChargeBack(c) => c.transaction_id(),
+            Mutation::Finalize(f) => f.transaction_id(),
+            Mutation::Release(r) => r.transaction_id(),
+            Mutation::Reject(r) => r.transaction_id(),
+        }
+    }
+
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Mutation::Dispute(d) => d.timestamp(),
+            Mutation::Resolve(r) => r.timestamp(),
+            Mutation::ChargeBack(c) => c.timestamp(),
+            Mutation::Finalize(f) => f.timestamp(),
+            Mutation::Release(r) => r.timestamp(),
+            Mutation::Reject(r) => r.timestamp(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::transaction::transaction_reader;
+    use crate::{
+        client::RawClientId,
+        transaction::{error::DeserializationError, transaction_reader, transactions},
+    };
 
     #[test]
     pub fn example_input_test() {
@@ -133,4 +229,21 @@ mod tests {
             assert!(tx.is_ok());
         }
     }
+
+    #[test]
+    fn test_id_overflow() {
+        let highest = RawClientId::MAX;
+        let data = format!("type,client,tx,amount\ndeposit,{highest},1,1\n");
+        assert!(transactions(data.as_bytes()).all(|tx| tx.is_ok()));
+
+        let data = format!(
+            "type,client,tx,amount\ndeposit,{},1,1\n",
+            u128::from(highest) + 1
+        );
+        let width = 8 * std::mem::size_of::<RawClientId>() as u32;
+        assert!(matches!(
+            transactions(data.as_bytes()).next(),
+            Some(Err(DeserializationError::IdOverflow { column: "client", width: w, .. })) if w == width
+        ));
+    }
 }
@@ -0,0 +1,293 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    fmt,
+};
+
+use super::{reader_builder, Transaction, TransactionId, TransactionRow};
+use crate::client::Client;
+
+/// Represents a problem found in a row of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// The row could not be read or decoded into a [`TransactionRow`].
+    Unreadable(String),
+    /// The row was decoded but is not a valid transaction, like a deposit without an amount.
+    Invalid(String),
+    /// The row has another number of columns than the header, which the reader accepts as it is flexible.
+    /// A mutation that leaves out the trailing amount is not reported.
+    ColumnCount { expected: usize, found: usize },
+    /// The id was already used by an earlier transfer.
+    DuplicateTransaction(TransactionId),
+    /// The mutation references an id that no earlier transfer used.
+    UnknownTransaction(TransactionId),
+    /// The mutation references the transfer of another client.
+    ClientMismatch {
+        tx: TransactionId,
+        owner: Client,
+        client: Client,
+    },
+    /// The mutation carries an amount, which is ignored.
+    MutationAmount(TransactionId),
+}
+
+impl Issue {
+    /// Returns the name of the kind of issue as used in the summary.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Issue::Unreadable(_) => "unreadable",
+            Issue::Invalid(_) => "invalid",
+            Issue::ColumnCount { .. } => "column_count",
+            Issue::DuplicateTransaction(_) => "duplicate_transaction",
+            Issue::UnknownTransaction(_) => "unknown_transaction",
+            Issue::ClientMismatch { .. } => "client_mismatch",
+            Issue::MutationAmount(_) => "mutation_amount",
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Unreadable(err) => write!(f, "could not read row: {err}"),
+            Issue::Invalid(err) => write!(f, "invalid transaction: {err}"),
+            Issue::ColumnCount { expected, found } => {
+                write!(f, "{found} columns where the header has {expected}")
+            }
+            Issue::DuplicateTransaction(tx) => {
+                write!(f, "transaction {} was already used", tx.id())
+            }
+            Issue::UnknownTransaction(tx) => {
+                write!(f, "transaction {} does not precede this mutation", tx.id())
+            }
+            Issue::ClientMismatch { tx, owner, client } => write!(
+                f,
+                "client {} references transaction {} of client {}",
+                client.id(),
+                tx.id(),
+                owner.id()
+            ),
+            Issue::MutationAmount(tx) => {
+                write!(f, "mutation of transaction {} carries an amount", tx.id())
+            }
+        }
+    }
+}
+
+/// Represents an issue and the line of the input it was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub line: u64,
+    pub issue: Issue,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.issue)
+    }
+}
+
+/// Represents the outcome of checking every row of an input.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub rows: u64,
+    pub transfers: u64,
+    pub mutations: u64,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// Returns the number of findings per kind of issue.
+    pub fn counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for finding in &self.findings {
+            *counts.entry(finding.issue.kind()).or_default() += 1;
+        }
+        counts
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "rows: {}", self.rows)?;
+        writeln!(f, "transfers: {}", self.transfers)?;
+        writeln!(f, "mutations: {}", self.mutations)?;
+        writeln!(f, "issues: {}", self.findings.len())?;
+        for (kind, count) in self.counts() {
+            writeln!(f, "  {kind}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks every row of the input like it would be read for processing, without applying any transaction.
+///
+/// Next to rows that can not be read, this finds the rows that processing would reject
+/// because of the order or ids of the transactions, and rows that are accepted but suspicious.
+/// Only the client of every transfer id is kept in memory.
+pub fn validate<R>(reader: R) -> Report
+where
+    R: std::io::Read,
+{
+    let mut report = Report::default();
+    let mut rdr = reader_builder().from_reader(reader);
+    let headers = match rdr.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => {
+            report.findings.push(Finding {
+                line: 1,
+                issue: Issue::Unreadable(err.to_string()),
+            });
+            return report;
+        }
+    };
+    let mut owners: HashMap<TransactionId, Client> = HashMap::new();
+
+    for record in rdr.records() {
+        report.rows += 1;
+        let mut found = |line, issue| report.findings.push(Finding { line, issue });
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                let line = err.position().map_or(0, |position| position.line());
+                found(line, Issue::Unreadable(err.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let mut columns = (record.len() != headers.len()).then(|| Issue::ColumnCount {
+            expected: headers.len(),
+            found: record.len(),
+        });
+        let row: TransactionRow = match record.deserialize(Some(&headers)) {
+            Ok(row) => row,
+            Err(err) => {
+                if let Some(issue) = columns {
+                    found(line, issue);
+                }
+                found(line, Issue::Unreadable(err.to_string()));
+                continue;
+            }
+        };
+        let tx = match Transaction::try_from(row) {
+            Ok(tx) => tx,
+            Err(err) => {
+                if let Some(issue) = columns {
+                    found(line, issue);
+                }
+                found(line, Issue::Invalid(err.to_string()));
+                continue;
+            }
+        };
+        // Mutations do not need the trailing amount, like in `dispute,1,1`
+        let amount_left_out =
+            record.len() + 1 == headers.len() && headers.get(record.len()) == Some("amount");
+        if matches!(tx, Transaction::Mutation(_)) && amount_left_out {
+            columns = None;
+        }
+        if let Some(issue) = columns {
+            found(line, issue);
+        }
+
+        let id = tx.transaction_id();
+        match tx {
+            Transaction::Transfer(transfer) => {
+                report.transfers += 1;
+                // Like processing, the first transfer keeps the id
+                match owners.entry(id) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(transfer.client());
+                    }
+                    Entry::Occupied(_) => found(line, Issue::DuplicateTransaction(id)),
+                }
+            }
+            Transaction::Mutation(mutation) => {
+                report.mutations += 1;
+                if row.amount.is_some() {
+                    found(line, Issue::MutationAmount(id));
+                }
+                match owners.get(&id) {
+                    None => found(line, Issue::UnknownTransaction(id)),
+                    Some(&owner) if owner != mutation.client() => found(
+                        line,
+                        Issue::ClientMismatch {
+                            tx: id,
+                            owner,
+                            client: mutation.client(),
+                        },
+                    ),
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Issue};
+    use crate::{client::Client, transaction::TransactionId};
+
+    #[test]
+    fn test_validate() {
+        let data = "type,client,tx,amount
+deposit,1,1,10
+deposit,2,1,5
+withdrawal,1,2
+dispute,1,3,
+dispute,2,1,
+resolve,1,1,4
+deposit,1,4,1,100,extra
+transfer,1,5,1
+chargeback,1,1
+deposit,1,6,not-a-number";
+        let report = validate(data.as_bytes());
+        assert_eq!(report.rows, 10);
+        assert_eq!(report.transfers, 3);
+        assert_eq!(report.mutations, 4);
+
+        let tx = TransactionId::new;
+        let columns = |found| Issue::ColumnCount { expected: 4, found };
+        let expected = [
+            (3, Some(Issue::DuplicateTransaction(tx(1)))),
+            (4, Some(columns(3))),
+            // A withdrawal without amount
+            (4, None),
+            (5, Some(Issue::UnknownTransaction(tx(3)))),
+            (
+                6,
+                Some(Issue::ClientMismatch {
+                    tx: tx(1),
+                    owner: Client::new(1),
+                    client: Client::new(2),
+                }),
+            ),
+            (7, Some(Issue::MutationAmount(tx(1)))),
+            (8, Some(columns(6))),
+            // An unknown type
+            (9, None),
+            // The chargeback without the trailing amount is read like processing does
+            // An amount that is not a number
+            (11, None),
+        ];
+        assert_eq!(report.findings.len(), expected.len());
+        for (finding, (line, issue)) in report.findings.iter().zip(expected) {
+            assert_eq!(finding.line, line, "{finding}");
+            match issue {
+                Some(issue) => assert_eq!(finding.issue, issue),
+                None => assert!(
+                    matches!(finding.issue, Issue::Unreadable(_) | Issue::Invalid(_)),
+                    "{finding}"
+                ),
+            }
+        }
+        assert_eq!(report.counts()["column_count"], 2);
+        assert!(!report.is_clean());
+
+        assert!(validate("type,client,tx,amount\ndeposit,1,1,1\n".as_bytes()).is_clean());
+    }
+}
@@ -1,7 +1,7 @@
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
-use super::TransactionId;
+use super::{Timestamp, TransactionId};
 use crate::client::Client;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
@@ -9,11 +9,17 @@ pub struct Withdrawal {
     client: Client,
     tx: TransactionId,
     amount: Decimal,
+    timestamp: Option<Timestamp>,
 }
 
 impl Withdrawal {
     pub fn new(client: Client, tx: TransactionId, amount: Decimal) -> Self {
-        Self { client, tx, amount }
+        Self {
+            client,
+            tx,
+            amount,
+            timestamp: None,
+        }
     }
     pub fn client(&self) -> Client {
         self.client
@@ -24,4 +30,11 @@ impl Withdrawal {
     pub fn amount(&self) -> Decimal {
         self.amount
     }
+    pub fn with_timestamp(mut self, timestamp: Option<Timestamp>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
 }
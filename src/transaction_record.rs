@@ -1,34 +1,114 @@
-use crate::error::TransactionError;
+use crate::{client::Client, error::TransactionError, policy::DisputePolicy};
 
-use super::transaction::{Mutation, Transfer};
+use super::transaction::{Mutation, Timestamp, Transfer};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TransactionRecord {
     tx: Transfer,
+    /// The deposit is held for review and its funds are not credited yet.
+    pending: bool,
     under_dispute: bool,
     charge_backed: bool,
+    /// The moment the current dispute was filed, if known.
+    disputed_at: Option<Timestamp>,
+    /// A client other than the owner that mutated the transaction.
+    foreign_client: Option<Client>,
 }
 
 impl TransactionRecord {
     pub fn new(tx: Transfer) -> Self {
         Self {
             tx,
+            pending: false,
             under_dispute: false,
             charge_backed: false,
+            disputed_at: None,
+            foreign_client: None,
         }
     }
+
+    /// Restores a record from its parts, used by ledgers that store records in another representation.
+    pub fn from_parts(
+        tx: Transfer,
+        under_dispute: bool,
+        charge_backed: bool,
+        disputed_at: Option<Timestamp>,
+        foreign_client: Option<Client>,
+    ) -> Self {
+        Self {
+            tx,
+            pending: false,
+            under_dispute,
+            charge_backed,
+            disputed_at,
+            foreign_client,
+        }
+    }
+
+    /// Marks whether the deposit is held for review until it is released or rejected.
+    pub fn with_pending(mut self, pending: bool) -> Self {
+        self.pending = pending;
+        self
+    }
+
     pub fn tx(&self) -> &Transfer {
         &self.tx
     }
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+    pub fn under_dispute(&self) -> bool {
+        self.under_dispute
+    }
+    pub fn charge_backed(&self) -> bool {
+        self.charge_backed
+    }
+    pub fn disputed_at(&self) -> Option<Timestamp> {
+        self.disputed_at
+    }
+    pub fn foreign_client(&self) -> Option<Client> {
+        self.foreign_client
+    }
 
     /// Mutates the transaction record with the provided mutation type.
     ///
     /// Returns an error if the mutation is not allowed on the transaction
-    pub fn mutate(&mut self, mutation: &Mutation) -> Result<(), TransactionError> {
+    /// or if a dispute falls outside the window of the `policy`.
+    pub fn mutate(
+        &mut self,
+        mutation: &Mutation,
+        policy: &DisputePolicy,
+    ) -> Result<(), TransactionError> {
+        // A pending deposit can only be released or rejected
+        if self.pending && !matches!(mutation, Mutation::Release(_) | Mutation::Reject(_)) {
+            tracing::error!(
+                "Could not mutate transaction {:?}, it is pending review",
+                self.tx
+            );
+            return Err(TransactionError::TransactionPending(
+                self.tx.transaction_id(),
+            ));
+        }
         match mutation {
-            Mutation::Dispute(_) => {
+            Mutation::Dispute(dispute) => {
                 if !self.charge_backed {
+                    if let (Some(window), Some(filed), Some(original)) =
+                        (policy.window(), dispute.timestamp(), self.tx.timestamp())
+                    {
+                        if filed.seconds_since(original) > window {
+                            tracing::error!(
+                                "Could not dispute transaction {:?} filed at {:?}, window of {}s expired",
+                                self.tx,
+                                filed,
+                                window
+                            );
+                            return Err(TransactionError::DisputeWindowExpired(
+                                self.tx.transaction_id(),
+                            ));
+                        }
+                    }
                     self.under_dispute = true;
+                    self.disputed_at = dispute.timestamp();
                 } else {
                     tracing::error!(
                         "Could not dispute transaction {:?} disputed: {}, charbacked: {}",
@@ -42,6 +122,7 @@ impl TransactionRecord {
             Mutation::Resolve(_) => {
                 if self.under_dispute && !self.charge_backed {
                     self.under_dispute = false;
+                    self.disputed_at = None;
                 } else {
                     tracing::error!(
                         "Could not resolve transaction {:?} disputed: {}, charbacked: {}",
@@ -56,6 +137,7 @@ impl TransactionRecord {
                 if self.under_dispute {
                     self.under_dispute = false;
                     self.charge_backed = true;
+                    self.disputed_at = None;
                 } else {
                     tracing::error!(
                         "Could not charge back transaction {:?} disputed: {}, charbacked: {}",
@@ -66,6 +148,42 @@ impl TransactionRecord {
                     return Err(TransactionError::ChargeBackError);
                 }
             }
+            Mutation::Finalize(_) => {
+                // An open dispute has to be settled before the transaction is final
+                if self.under_dispute {
+                    tracing::error!(
+                        "Could not finalize transaction {:?} disputed: {}",
+                        self.tx,
+                        self.under_dispute
+                    );
+                    return Err(TransactionError::FinalizeError);
+                }
+            }
+            Mutation::Release(_) => {
+                if self.pending {
+                    self.pending = false;
+                } else {
+                    tracing::error!(
+                        "Could not release transaction {:?}, it is not pending",
+                        self.tx
+                    );
+                    return Err(TransactionError::ReleaseError);
+                }
+            }
+            Mutation::Reject(_) => {
+                if self.pending {
+                    self.pending = false;
+                } else {
+                    tracing::error!(
+                        "Could not reject transaction {:?}, it is not pending",
+                        self.tx
+                    );
+                    return Err(TransactionError::RejectError);
+                }
+            }
+        }
+        if mutation.client() != self.tx.client() {
+            self.foreign_client = Some(mutation.client());
         }
         Ok(())
     }
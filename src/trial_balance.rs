@@ -1,83 +1,818 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use rust_decimal::Decimal;
 
 use crate::{
-    account::Account,
+    account::{round, Account},
+    account_store::AccountStore,
     client::Client,
     error::TransactionError,
-    transaction::{Transaction, TransactionId},
+    journal::Journal,
+    ledger::{IdRanges, Ledger},
+    observer::{Balances, Event, Observer, Observers},
+    policy::{DisputePolicy, RetentionPolicy},
+    rules::{Action, Screener},
+    statement::StatementLine,
+    stats::Stats,
+    transaction::{resolve::Resolve, Mutation, Timestamp, Transaction, TransactionId, Transfer},
     transaction_record::TransactionRecord,
+    verify::Violation,
 };
 
 /// Represents all accounts in the system and the transactions that have been processed.
 /// The ledger does not keep transaction mutations but merely the current state of the transaction.
 /// This keeps the ledger simple and reduces the overal size of the structure.
+/// A statement with every handled transaction per client can be kept by enabling [`TrialBalance::with_history`].
 #[derive(Debug)]
 pub struct TrialBalance {
     // Hashmaps are the recommended data structure for this task.
-    // A Vec could be faster for accounts if max number of clients is known. A vec could also cause wasted space.
-    accounts: HashMap<Client, Account>,
-    ledger: HashMap<TransactionId, TransactionRecord>,
+    // A Vec could be faster for accounts if max number of clients is known, see `DenseAccountStore`.
+    accounts: Box<dyn AccountStore>,
+    ledger: Box<dyn Ledger>,
+    policy: DisputePolicy,
+    retention: RetentionPolicy,
+    // Transfers that can still be evicted in the order they were inserted, only kept when retention is enabled.
+    // May contain transfers that have been finalized since.
+    retained: VecDeque<(Option<Timestamp>, TransactionId)>,
+    // Number of charged back records that were taken out of the queue but stay in the ledger.
+    pinned: usize,
+    // Transfers that have been evicted from the ledger.
+    evicted: IdRanges,
+    // Disputes that will be resolved automatically, ordered by their deadline.
+    open_disputes: BTreeSet<(Timestamp, TransactionId)>,
+    // Resolves that were generated because a dispute passed its deadline.
+    auto_resolved: Vec<Resolve>,
+    // Number of transactions handled so far, including the generated ones.
+    seq: u64,
+    // Statement lines per client, only kept when history is enabled.
+    history: Option<HashMap<Client, Vec<StatementLine>>>,
+    // Double-entry postings of every applied transaction, only kept when the journal is enabled.
+    journal: Option<Journal>,
+    // Rules that transactions are screened with before they are applied.
+    screener: Option<Screener>,
+    // Hooks that are notified of the outcome of every transaction.
+    observers: Observers,
+    // Number of transactions after which the invariants are verified.
+    verify_every: Option<u64>,
+    since_verify: u64,
+    // Violations found by the most recent periodic verification.
+    violations: Vec<Violation>,
+    // Counts of the outcomes and amounts of the handled transactions, only kept when enabled.
+    stats: Option<Stats>,
+}
+
+impl Default for TrialBalance {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TrialBalance {
     pub fn new() -> Self {
         Self {
-            accounts: HashMap::with_capacity(1000),
-            ledger: HashMap::with_capacity(100000),
+            accounts: Box::new(HashMap::<Client, Account>::with_capacity(1000)),
+            ledger: Box::new(HashMap::<TransactionId, TransactionRecord>::with_capacity(
+                100000,
+            )),
+            policy: DisputePolicy::default(),
+            retention: RetentionPolicy::default(),
+            retained: VecDeque::new(),
+            pinned: 0,
+            evicted: IdRanges::new(),
+            open_disputes: BTreeSet::new(),
+            auto_resolved: Vec::new(),
+            seq: 0,
+            history: None,
+            journal: None,
+            screener: None,
+            observers: Observers::default(),
+            verify_every: None,
+            since_verify: 0,
+            violations: Vec::new(),
+            stats: None,
         }
     }
 
+    /// Verifies the invariants after every `n` handled transactions.
+    /// Violations are logged and can be retrieved via [`TrialBalance::violations`].
+    pub fn with_verify_every(mut self, n: u64) -> Self {
+        self.verify_every = Some(n.max(1));
+        self
+    }
+
+    /// Returns the violations found by the most recent periodic verification.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Screens every transaction with the rules of the screener before it is applied.
+    ///
+    /// Deposits held by a rule are kept as pending funds until a `release` or `reject` settles them.
+    /// Other held and rejected transactions are not applied and fail with the rule that fired.
+    pub fn with_screener(mut self, screener: Screener) -> Self {
+        self.screener = Some(screener);
+        self
+    }
+
+    /// Returns the screener, or `None` when screening is not enabled.
+    pub fn screener(&self) -> Option<&Screener> {
+        self.screener.as_ref()
+    }
+
+    /// Registers an observer that is notified of the outcome of every transaction.
+    ///
+    /// Multiple observers can be registered, they are notified in the order they were added.
+    pub fn with_observer<O: Observer + 'static>(mut self, observer: O) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Records balanced double-entry postings for every applied transaction.
+    pub fn with_journal(mut self) -> Self {
+        self.journal = Some(Journal::new());
+        self
+    }
+
+    /// Returns the journal, or `None` when the journal is not enabled.
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Collects statistics of the outcomes and amounts of every transaction that is handled.
+    /// The statistics collected so far are kept when they are already enabled.
+    pub fn with_stats(mut self) -> Self {
+        self.stats.get_or_insert_with(Stats::new);
+        self
+    }
+
+    /// Returns the statistics up to now, or `None` when statistics are not enabled.
+    pub fn stats(&self) -> Option<Stats> {
+        let mut stats = self.stats.clone()?;
+        stats.finish(
+            self.accounts
+                .iter()
+                .filter(|account| account.locked())
+                .count(),
+        );
+        Some(stats)
+    }
+
+    /// Returns the statistics as they are collected, without the locked accounts and throughput that
+    /// [`TrialBalance::stats`] takes, or `None` when statistics are not enabled.
+    pub fn collected_stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Counts a row of the input that could not be read as a transaction in the statistics.
+    pub fn record_invalid(&mut self) {
+        if let Some(stats) = self.stats.as_mut() {
+            stats.record_invalid();
+        }
+    }
+
+    /// Keeps a statement line for every transaction that is handled.
+    pub fn with_history(mut self) -> Self {
+        self.history = Some(HashMap::new());
+        self
+    }
+
+    /// Returns the statement of the client, or `None` when history is not enabled.
+    pub fn statement(&self, client: Client) -> Option<&[StatementLine]> {
+        self.history.as_ref().map(|history| {
+            history
+                .get(&client)
+                .map(|lines| lines.as_slice())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Stores the transaction records in the ledger instead of the default [`HashMap`].
+    pub fn with_ledger<L: Ledger + 'static>(mut self, ledger: L) -> Self {
+        self.ledger = Box::new(ledger);
+        self
+    }
+
+    /// Starts from the accounts instead of an empty set, for example when resuming a persisted run.
+    pub fn with_accounts<I: IntoIterator<Item = Account>>(mut self, accounts: I) -> Self {
+        for account in accounts {
+            self.accounts.insert(account);
+        }
+        self
+    }
+
+    /// Stores the accounts in the store instead of the default [`HashMap`].
+    ///
+    /// Accounts that were already opened are moved into the store.
+    pub fn with_account_store<S: AccountStore + 'static>(mut self, store: S) -> Self {
+        let accounts = std::mem::replace(&mut self.accounts, Box::new(store));
+        self.with_accounts(accounts.snapshot())
+    }
+
+    /// Sets the dispute time limits that are applied to incoming mutations.
+    pub fn with_dispute_policy(mut self, policy: DisputePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Evicts the records of transfers from the ledger once they pass the limits of the policy.
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Returns the ids of the transfers that have been evicted from the ledger or finalized.
+    pub fn evicted(&self) -> &IdRanges {
+        &self.evicted
+    }
+
+    /// Returns the resolves that were generated for disputes that stayed open past their deadline.
+    pub fn auto_resolved(&self) -> &[Resolve] {
+        &self.auto_resolved
+    }
+
     pub fn to_csv<W>(&self, w: &mut W) -> Result<(), csv::Error>
     where
         W: std::io::Write,
     {
         let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(w);
         self.accounts
-            .values()
+            .iter()
             .try_for_each(|account| wtr.serialize(account))
     }
 
+    /// Returns the number of transaction records in the ledger.
+    pub fn ledger_len(&self) -> usize {
+        self.ledger.len()
+    }
+
+    /// Returns the account of the client, or `None` when the client has no account.
+    pub fn account(&self, client: Client) -> Option<&Account> {
+        self.accounts.get(client)
+    }
+
+    /// Returns all accounts in the order of the account store, without copying them.
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.iter()
+    }
+
+    /// Returns whether the id is taken by a transaction in the ledger or by one that was evicted.
+    pub fn contains_transaction(&self, id: TransactionId) -> bool {
+        self.ledger.contains(id) || self.evicted.contains(id)
+    }
+
+    /// Returns a copy of all accounts ordered by client.
+    pub fn snapshot(&self) -> Vec<Account> {
+        self.accounts.snapshot()
+    }
+
     /// Handles a transaction and updates the accounts and ledger accordingly.
     pub fn handle_transaction(&mut self, tx: Transaction) -> Result<(), TransactionError> {
-        let account = self
-            .accounts
-            .entry(tx.client())
-            .or_insert(Account::new(tx.client()));
+        self.handle(tx, None)
+    }
+
+    /// Handles a transfer by a client of this trial balance whose id may be taken by a transaction kept
+    /// by `owner`, like when the clients are split over shards. The transfer is screened like any other
+    /// and is a duplicate when either trial balance knows its id.
+    pub fn handle_foreign_transfer(
+        &mut self,
+        owner: &mut TrialBalance,
+        transfer: Transfer,
+    ) -> Result<(), TransactionError> {
+        if let Some(now) = transfer.timestamp() {
+            owner.expire_disputes(now);
+        }
+        self.handle(Transaction::Transfer(transfer), Some(owner))
+    }
+
+    /// Handles a mutation by a client of this trial balance of a transaction that is kept by `owner`,
+    /// like when the clients are split over shards. The outcome is the same as when a single trial
+    /// balance handles both: the record is mutated in `owner` and the account of the client in `self`.
+    pub fn handle_foreign_mutation(
+        &mut self,
+        owner: &mut TrialBalance,
+        mutation: Mutation,
+    ) -> Result<(), TransactionError> {
+        if let Some(now) = mutation.timestamp() {
+            owner.expire_disputes(now);
+        }
+        self.handle(Transaction::Mutation(mutation), Some(owner))
+    }
+
+    fn handle(
+        &mut self,
+        tx: Transaction,
+        mut owner: Option<&mut TrialBalance>,
+    ) -> Result<(), TransactionError> {
+        if let Some(now) = tx.timestamp() {
+            self.expire_disputes(now);
+        }
+
+        let now = tx.timestamp();
+        let copy = (self.history.is_some() || self.stats.is_some()).then(|| tx.clone());
+        let before = self.balances(&tx);
+        let screened = self.screener.is_some().then(|| tx.clone());
+        let res = self.screen(&tx).and_then(|pending| {
+            self.apply(tx, pending, owner.as_deref_mut())
+                .map(|()| pending)
+        });
+        // Transactions the account rejects do not count towards the history of the rules
+        if let (Some(screener), Some(tx), Ok(false)) = (self.screener.as_mut(), screened, &res) {
+            screener.commit(&tx);
+        }
+        let res = res.map(|_| ());
+        if let Some(tx) = copy {
+            self.count(&tx, &res, before);
+            if self.history.is_some() {
+                let amount = match owner {
+                    Some(owner) => owner.amount(&tx),
+                    None => self.amount(&tx),
+                };
+                self.record(tx, res.clone(), amount);
+            }
+        }
+        self.seq += 1;
+        if self.retention.is_enabled() {
+            self.enforce_retention(now);
+        }
+
+        if let Some(every) = self.verify_every {
+            self.since_verify += 1;
+            if self.since_verify >= every {
+                self.since_verify = 0;
+                self.violations = self.verify().err().unwrap_or_default();
+                for violation in &self.violations {
+                    tracing::error!(
+                        "Invariant violated after {} transactions: {}",
+                        self.seq,
+                        violation
+                    );
+                }
+            }
+        }
+        res
+    }
+
+    /// Screens the transaction with the rules and returns whether it is a deposit to hold as pending,
+    /// or the error of a rule that keeps it from being applied.
+    fn screen(&mut self, tx: &Transaction) -> Result<bool, TransactionError> {
+        let Some(hit) = self
+            .screener
+            .as_mut()
+            .and_then(|screener| screener.screen(tx))
+        else {
+            return Ok(false);
+        };
+        let error = match (hit.action, tx) {
+            (Action::Flag, _) => return Ok(false),
+            (Action::Hold, Transaction::Transfer(Transfer::Deposit(_))) => return Ok(true),
+            (Action::Hold, _) => TransactionError::HeldByRule(hit.rule),
+            (Action::Reject, _) => TransactionError::RejectedByRule(hit.rule),
+        };
+        let account = self.accounts.get_or_insert(tx.client());
+        if let (Transaction::Transfer(transfer), false) = (tx, self.observers.is_empty()) {
+            self.observers.notify(Event::TransferRejected {
+                client: account.client(),
+                tx: transfer.transaction_id(),
+                amount: transfer.amount(),
+                error: error.clone(),
+            });
+        }
+        Err(error)
+    }
+
+    /// Applies the transaction, the record a mutation refers to is kept by `owner` when given.
+    fn apply(
+        &mut self,
+        tx: Transaction,
+        pending: bool,
+        mut owner: Option<&mut TrialBalance>,
+    ) -> Result<(), TransactionError> {
+        let account = self.accounts.get_or_insert(tx.client());
 
         match tx {
             Transaction::Transfer(transfer) => {
                 tracing::debug!("Handling transfer {:?}", transfer);
-                if let Entry::Vacant(e) = self.ledger.entry(transfer.transaction_id()) {
-                    let res = account.handle_transfer(&transfer);
+                let tx_id = transfer.transaction_id();
+                let foreign = owner
+                    .as_deref()
+                    .is_some_and(|owner| owner.contains_transaction(tx_id));
+                if !foreign && !self.ledger.contains(tx_id) && !self.evicted.contains(tx_id) {
+                    let before = Balances::of(account);
+                    let res = match &transfer {
+                        Transfer::Deposit(deposit) if pending => {
+                            account.handle_pending(deposit);
+                            Ok(())
+                        }
+                        transfer => account.handle_transfer(transfer),
+                    };
+                    if !self.observers.is_empty() {
+                        self.observers.notify(match &res {
+                            Ok(()) if pending => Event::TransferPending {
+                                client: account.client(),
+                                tx: tx_id,
+                                amount: transfer.amount(),
+                                before,
+                                after: Balances::of(account),
+                            },
+                            Ok(()) => Event::TransferApplied {
+                                client: account.client(),
+                                tx: tx_id,
+                                amount: transfer.amount(),
+                                before,
+                                after: Balances::of(account),
+                            },
+                            Err(error) => Event::TransferRejected {
+                                client: account.client(),
+                                tx: tx_id,
+                                amount: transfer.amount(),
+                                error: error.clone(),
+                            },
+                        });
+                    }
+                    if let (Ok(()), Some(journal)) = (&res, self.journal.as_mut()) {
+                        match &transfer {
+                            Transfer::Deposit(deposit) if pending => journal.post_pending(deposit),
+                            transfer => journal.post_transfer(transfer),
+                        }
+                    }
                     // Stick the transaction into the ledger
                     // This might not be desired if you only want to keep track of succesful transactions.
                     // Alternatively, it is possible to keep track of success on the transaction in the ledger
-                    e.insert(TransactionRecord::new(transfer));
+                    if self.retention.is_enabled() {
+                        self.retained.push_back((transfer.timestamp(), tx_id));
+                    }
+                    self.ledger
+                        .insert(TransactionRecord::new(transfer).with_pending(pending));
                     // Return the result of the transfer handling
                     res?;
                 } else {
-                    return Err(TransactionError::DuplicateTransaction(
-                        transfer.transaction_id(),
-                    ));
+                    let error = TransactionError::DuplicateTransaction(tx_id);
+                    if !self.observers.is_empty() {
+                        self.observers.notify(Event::TransferRejected {
+                            client: transfer.client(),
+                            tx: tx_id,
+                            amount: transfer.amount(),
+                            error: error.clone(),
+                        });
+                    }
+                    return Err(error);
                 }
             }
             Transaction::Mutation(mutation) => {
                 tracing::debug!("Handling mutation {:?}", mutation);
-                if let Some(tx_record) = self.ledger.get_mut(&mutation.transaction_id()) {
-                    tracing::debug!("Found transaction record {:?}", tx_record);
-                    // Mutate the transaction record
-                    tx_record.mutate(&mutation)?;
+                // Mutate a copy of the transaction record
+                let tx_record = match owner.as_deref_mut() {
+                    Some(owner) => owner.mutate_record(&mutation)?,
+                    None => self.mutate_record(&mutation)?,
+                };
+                // Finalizing settles the transaction without touching the account
+                if !matches!(mutation, Mutation::Finalize(_)) {
                     // update the account to reflect mutation
-                    account.handle_mutation(&mutation, tx_record.tx().amount())?;
-                } else {
-                    return Err(TransactionError::MissingTransaction(
-                        mutation.transaction_id(),
-                    ));
+                    let amount = tx_record.tx().amount();
+                    let account = self.accounts.get_or_insert(mutation.client());
+                    let before = Balances::of(account);
+                    account.handle_mutation(&mutation, amount)?;
+                    notify_mutation(&mut self.observers, &mutation, amount, before, account);
+                    if let Some(journal) = self.journal.as_mut() {
+                        journal.post_mutation(account.client(), &mutation, amount);
+                    }
+                }
+                // Only write the mutated record back once the account accepted the mutation
+                match owner {
+                    Some(owner) => owner.commit_record(&mutation, tx_record),
+                    None => self.commit_record(&mutation, tx_record),
                 }
             }
         }
         Ok(())
     }
+
+    /// Returns the record of the transaction with the mutation applied, without writing it back.
+    fn mutate_record(
+        &mut self,
+        mutation: &Mutation,
+    ) -> Result<TransactionRecord, TransactionError> {
+        let tx_id = mutation.transaction_id();
+        let Some(mut tx_record) = self.ledger.get(tx_id) else {
+            return Err(if self.evicted.contains(tx_id) {
+                TransactionError::TransactionFinalized(tx_id)
+            } else {
+                TransactionError::MissingTransaction(tx_id)
+            });
+        };
+        tracing::debug!("Found transaction record {:?}", tx_record);
+        tx_record.mutate(mutation, &self.policy)?;
+        Ok(tx_record)
+    }
+
+    /// Writes the mutated record back to the ledger, or evicts it when the mutation settled the transaction.
+    fn commit_record(&mut self, mutation: &Mutation, tx_record: TransactionRecord) {
+        let tx_id = mutation.transaction_id();
+        match mutation {
+            // Charged back records are already final and are kept to show why the account is locked
+            Mutation::Finalize(_) if tx_record.charge_backed() => {}
+            // The record of a rejected deposit is discarded like a finalized one
+            Mutation::Finalize(_) | Mutation::Reject(_) => self.evict(tx_id),
+            _ => {
+                // Schedule the dispute for automatic resolution
+                if let (Mutation::Dispute(_), Some(after), Some(disputed_at)) = (
+                    mutation,
+                    self.policy.auto_resolve_after(),
+                    tx_record.disputed_at(),
+                ) {
+                    self.open_disputes
+                        .insert((disputed_at.add_secs(after), tx_id));
+                }
+                self.ledger.insert(tx_record);
+            }
+        }
+    }
+
+    /// Records a transaction that was rejected before it reached the trial balance.
+    ///
+    /// The account of the client is opened like for any other transaction
+    /// and the rejection shows up in the statement when history is enabled.
+    pub fn reject(&mut self, tx: Transaction, err: TransactionError) {
+        self.accounts.get_or_insert(tx.client());
+        let err = Err(err);
+        let balances = self.balances(&tx);
+        self.count(&tx, &err, balances);
+        if self.history.is_some() {
+            let amount = self.amount(&tx);
+            self.record(tx, err, amount);
+        }
+        self.seq += 1;
+    }
+
+    /// Resolves all disputes that have been open past the auto resolve deadline at `now`.
+    ///
+    /// The generated resolves can be retrieved via [`TrialBalance::auto_resolved`].
+    pub fn expire_disputes(&mut self, now: Timestamp) {
+        let Some(after) = self.policy.auto_resolve_after() else {
+            return;
+        };
+
+        while let Some(&(deadline, tx_id)) = self.open_disputes.first() {
+            if deadline > now {
+                break;
+            }
+            self.open_disputes.pop_first();
+
+            let Some(mut tx_record) = self.ledger.get(tx_id) else {
+                continue;
+            };
+            // The dispute could have been settled or filed again since it was scheduled
+            if !tx_record.under_dispute()
+                || tx_record.disputed_at().map(|at| at.add_secs(after)) != Some(deadline)
+            {
+                continue;
+            }
+
+            let client = tx_record.tx().client();
+            let account = self.accounts.get_or_insert(client);
+            if account.locked() {
+                tracing::debug!("Not auto resolving {:?}, account is locked", tx_id);
+                continue;
+            }
+
+            let resolve = Resolve::new(client, tx_id).with_timestamp(Some(deadline));
+            let mutation = Mutation::Resolve(resolve);
+            tracing::debug!("Auto resolving dispute {:?}", resolve);
+            let amount = tx_record.tx().amount();
+            let before = Balances::of(account);
+            let res = tx_record
+                .mutate(&mutation, &self.policy)
+                .and_then(|_| account.handle_mutation(&mutation, amount))
+                .map(|_| self.ledger.insert(tx_record));
+            match &res {
+                Ok(()) => {
+                    notify_mutation(&mut self.observers, &mutation, amount, before, account);
+                    if let Some(journal) = self.journal.as_mut() {
+                        journal.post_mutation(client, &mutation, amount);
+                    }
+                    self.auto_resolved.push(resolve);
+                }
+                Err(err) => tracing::error!("Could not auto resolve {:?}: {:?}", tx_id, err),
+            }
+            if self.history.is_some() {
+                let tx = Transaction::Mutation(mutation);
+                let amount = self.amount(&tx);
+                self.record(tx, res, amount);
+            }
+            self.seq += 1;
+        }
+    }
+
+    /// Evicts the oldest transfers while they exceed the limits of the retention policy at `now`.
+    ///
+    /// Transfers are evicted in the order they were inserted, so a transfer without a timestamp
+    /// holds up the eviction by age of the transfers after it until it is evicted by count.
+    fn enforce_retention(&mut self, now: Option<Timestamp>) {
+        // Records under dispute go to the back of the queue, each is only looked at once per call
+        let mut deferred = 0;
+        while let Some(&(timestamp, tx_id)) = self.retained.front() {
+            let over_count = self
+                .retention
+                .max_records()
+                .is_some_and(|max| self.ledger.len() - self.pinned > max);
+            let expired = match (self.retention.max_age(), timestamp, now) {
+                (Some(max_age), Some(timestamp), Some(now)) => {
+                    now.seconds_since(timestamp) > max_age
+                }
+                _ => false,
+            };
+            if (!over_count && !expired) || deferred >= self.retained.len() {
+                break;
+            }
+            self.retained.pop_front();
+
+            let Some(tx_record) = self.ledger.get(tx_id) else {
+                // Finalized since it was queued
+                continue;
+            };
+            if tx_record.under_dispute() || tx_record.pending() {
+                self.retained.push_back((timestamp, tx_id));
+                deferred += 1;
+            } else if tx_record.charge_backed() {
+                self.pinned += 1;
+            } else {
+                tracing::debug!("Evicting transaction {:?}", tx_id);
+                self.evict(tx_id);
+            }
+        }
+    }
+
+    fn evict(&mut self, tx_id: TransactionId) {
+        self.ledger.remove(tx_id);
+        self.evicted.insert(tx_id);
+    }
+
+    /// Checks the global invariants of the accounts and ledger and returns every violation found:
+    /// - the held funds of an account equal the sum of its disputed transactions
+    /// - the pending funds of an account equal the sum of its deposits held for review
+    /// - the written total equals the written available and held funds
+    /// - a locked account has at least one charged back transaction
+    /// - a transaction is only mutated by the client that owns it
+    ///
+    /// This walks the entire ledger, which is expensive for large inputs.
+    pub fn verify(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        let mut foreign = Vec::new();
+
+        // The disputed amount, disputed transactions and whether any transaction was charged back
+        let mut per_client: HashMap<Client, (Decimal, Vec<TransactionId>, bool)> = HashMap::new();
+        // The amount and transactions held for review
+        let mut pending: HashMap<Client, (Decimal, Vec<TransactionId>)> = HashMap::new();
+        for tx_record in self.ledger.records() {
+            let tx_id = tx_record.tx().transaction_id();
+            let owner = tx_record.tx().client();
+            let (disputed, transactions, charged_back) = per_client.entry(owner).or_default();
+            if tx_record.under_dispute() {
+                *disputed += tx_record.tx().amount();
+                transactions.push(tx_id);
+            }
+            *charged_back |= tx_record.charge_backed();
+            if tx_record.pending() {
+                let (amount, transactions) = pending.entry(owner).or_default();
+                *amount += tx_record.tx().amount();
+                transactions.push(tx_id);
+            }
+            if let Some(client) = tx_record.foreign_client() {
+                foreign.push((tx_id, owner, client));
+            }
+        }
+
+        let mut accounts: Vec<&Account> = self.accounts.iter().collect();
+        accounts.sort_by_key(|account| account.client());
+        for account in accounts {
+            let client = account.client();
+            let (disputed, mut transactions, charged_back) =
+                per_client.remove(&client).unwrap_or_default();
+            if account.held() != disputed {
+                transactions.sort();
+                violations.push(Violation::HeldMismatch {
+                    client,
+                    held: account.held(),
+                    disputed,
+                    transactions,
+                });
+            }
+            let (held_for_review, mut transactions) = pending.remove(&client).unwrap_or_default();
+            if account.pending() != held_for_review {
+                transactions.sort();
+                violations.push(Violation::PendingMismatch {
+                    client,
+                    pending: account.pending(),
+                    held_for_review,
+                    transactions,
+                });
+            }
+            let (available, held, total) = (
+                round(account.available()),
+                round(account.held()),
+                round(account.total()),
+            );
+            if available + held != total {
+                violations.push(Violation::TotalMismatch {
+                    client,
+                    available,
+                    held,
+                    total,
+                });
+            }
+            if account.locked() && !charged_back {
+                violations.push(Violation::LockedWithoutChargeBack { client });
+            }
+        }
+
+        foreign.sort();
+        violations.extend(foreign.into_iter().map(|(transaction, owner, client)| {
+            Violation::ForeignMutation {
+                transaction,
+                owner,
+                client,
+            }
+        }));
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Returns the balances of the account of the transaction when statistics are collected.
+    fn balances(&mut self, tx: &Transaction) -> Option<Balances> {
+        self.stats.as_ref()?;
+        Some(Balances::of(self.accounts.get_or_insert(tx.client())))
+    }
+
+    /// Adds the outcome of the transaction to the statistics, `before` holds the balances before it was handled.
+    fn count(
+        &mut self,
+        tx: &Transaction,
+        res: &Result<(), TransactionError>,
+        before: Option<Balances>,
+    ) {
+        let (Some(stats), Some(before)) = (self.stats.as_mut(), before) else {
+            return;
+        };
+        let after = Balances::of(self.accounts.get_or_insert(tx.client()));
+        stats.record(tx, res, before, after);
+    }
+
+    /// Returns the amount of the transaction, for a mutation the amount of the record it refers to.
+    fn amount(&mut self, tx: &Transaction) -> Option<Decimal> {
+        match tx {
+            Transaction::Transfer(transfer) => Some(transfer.amount()),
+            Transaction::Mutation(mutation) => self
+                .ledger
+                .get(mutation.transaction_id())
+                .map(|tx_record| tx_record.tx().amount()),
+        }
+    }
+
+    /// Adds a statement line for the handled transaction to the history of its client.
+    fn record(
+        &mut self,
+        tx: Transaction,
+        res: Result<(), TransactionError>,
+        amount: Option<Decimal>,
+    ) {
+        let Some(history) = self.history.as_mut() else {
+            return;
+        };
+        let Some(account) = self.accounts.get(tx.client()) else {
+            return;
+        };
+        history
+            .entry(tx.client())
+            .or_default()
+            .push(StatementLine::new(self.seq, tx, amount, res, account));
+    }
+}
+
+/// Notifies the observers of a mutation that was applied to the account, and of the account being locked by it.
+fn notify_mutation(
+    observers: &mut Observers,
+    mutation: &Mutation,
+    amount: Decimal,
+    before: Balances,
+    account: &Account,
+) {
+    if observers.is_empty() {
+        return;
+    }
+    let after = Balances::of(account);
+    if let Some(event) = Event::mutation(mutation, amount, before, after) {
+        observers.notify(event);
+    }
+    if after.locked && !before.locked {
+        observers.notify(Event::AccountLocked {
+            client: account.client(),
+            tx: mutation.transaction_id(),
+        });
+    }
 }
 
 #[cfg(test)]
@@ -86,12 +821,23 @@ mod tests {
 
     use crate::{
         client::Client,
+        policy::{DisputePolicy, RetentionPolicy},
+        rules::{RuleSet, Screener},
         transaction::{
-            charge_back::ChargeBack, deposit::Deposit, dispute::Dispute, resolve::Resolve,
-            withdrawal::Withdrawal, Mutation, Transaction, TransactionId, Transfer,
+            charge_back::ChargeBack,
+            deposit::Deposit,
+            dispute::Dispute,
+            finalize::Finalize,
+            resolve::{self, Resolve},
+            transactions,
+            withdrawal::Withdrawal,
+            Mutation, Timestamp, Transaction, TransactionId, Transfer,
         },
+        verify::Violation,
     };
 
+    const DAY: u64 = 24 * 60 * 60;
+
     #[test]
     fn test_trial_balance() {
         let transactions = vec![
@@ -150,7 +896,7 @@ mod tests {
             )),
         ];
         let mut trial_balance = super::TrialBalance::new();
-        for (tx, expected_res) in transactions.into_iter().zip(results.into_iter()) {
+        for (tx, expected_res) in transactions.into_iter().zip(results) {
             let res = trial_balance.handle_transaction(tx);
             assert_eq!(res, expected_res);
         }
@@ -206,11 +952,7 @@ mod tests {
             )),
         ];
         let mut trial_balance = super::TrialBalance::new();
-        for (index, (tx, expected_res)) in transactions
-            .into_iter()
-            .zip(results.into_iter())
-            .enumerate()
-        {
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
             let res = trial_balance.handle_transaction(tx);
             assert_eq!(res, expected_res, "Failed on index {}", index);
         }
@@ -251,11 +993,7 @@ mod tests {
             Err(crate::error::TransactionError::ResolveError),
         ];
         let mut trial_balance = super::TrialBalance::new();
-        for (index, (tx, expected_res)) in transactions
-            .into_iter()
-            .zip(results.into_iter())
-            .enumerate()
-        {
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
             let res = trial_balance.handle_transaction(tx);
             assert_eq!(res, expected_res, "Failed on index {}", index);
         }
@@ -290,11 +1028,7 @@ mod tests {
             Err(crate::error::TransactionError::DisputeError),
         ];
         let mut trial_balance = super::TrialBalance::new();
-        for (index, (tx, expected_res)) in transactions
-            .into_iter()
-            .zip(results.into_iter())
-            .enumerate()
-        {
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
             let res = trial_balance.handle_transaction(tx);
             assert_eq!(res, expected_res, "Failed on index {}", index);
         }
@@ -336,13 +1070,519 @@ mod tests {
             Ok(()),
         ];
         let mut trial_balance = super::TrialBalance::new();
-        for (index, (tx, expected_res)) in transactions
-            .into_iter()
-            .zip(results.into_iter())
-            .enumerate()
-        {
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
+            let res = trial_balance.handle_transaction(tx);
+            assert_eq!(res, expected_res, "Failed on index {}", index);
+        }
+    }
+
+    #[test]
+    fn test_dispute_window_expired() {
+        let at = |day: u64| Some(Timestamp::from_secs(day * DAY));
+        let transactions = vec![
+            Transaction::Transfer(Transfer::Deposit(
+                Deposit::new(Client::new(1), TransactionId::new(1), Decimal::new(100, 0))
+                    .with_timestamp(at(0)),
+            )),
+            Transaction::Transfer(Transfer::Deposit(
+                Deposit::new(Client::new(1), TransactionId::new(2), Decimal::new(100, 0))
+                    .with_timestamp(at(100)),
+            )),
+            // Filed 121 days after the deposit
+            Transaction::Mutation(Mutation::Dispute(
+                Dispute::new(Client::new(1), TransactionId::new(1)).with_timestamp(at(121)),
+            )),
+            // Filed 21 days after the deposit
+            Transaction::Mutation(Mutation::Dispute(
+                Dispute::new(Client::new(1), TransactionId::new(2)).with_timestamp(at(121)),
+            )),
+            // Without a timestamp the window can not be enforced
+            Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(1),
+                TransactionId::new(1),
+            ))),
+        ];
+        let results = vec![
+            Ok(()),
+            Ok(()),
+            Err(crate::error::TransactionError::DisputeWindowExpired(
+                TransactionId::new(1),
+            )),
+            Ok(()),
+            Ok(()),
+        ];
+        let mut trial_balance = super::TrialBalance::new()
+            .with_dispute_policy(DisputePolicy::new().with_window(120 * DAY));
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
+            let res = trial_balance.handle_transaction(tx);
+            assert_eq!(res, expected_res, "Failed on index {}", index);
+        }
+    }
+
+    #[test]
+    fn test_dispute_auto_resolve() {
+        let at = |day: u64| Some(Timestamp::from_secs(day * DAY));
+        let transactions = vec![
+            Transaction::Transfer(Transfer::Deposit(
+                Deposit::new(Client::new(1), TransactionId::new(1), Decimal::new(100, 0))
+                    .with_timestamp(at(0)),
+            )),
+            Transaction::Transfer(Transfer::Deposit(
+                Deposit::new(Client::new(2), TransactionId::new(2), Decimal::new(100, 0))
+                    .with_timestamp(at(0)),
+            )),
+            Transaction::Mutation(Mutation::Dispute(
+                Dispute::new(Client::new(1), TransactionId::new(1)).with_timestamp(at(1)),
+            )),
+            Transaction::Mutation(Mutation::Dispute(
+                Dispute::new(Client::new(2), TransactionId::new(2)).with_timestamp(at(1)),
+            )),
+            Transaction::Mutation(Mutation::ChargeBack(
+                ChargeBack::new(Client::new(2), TransactionId::new(2)).with_timestamp(at(5)),
+            )),
+            // Passes the deadline of the dispute on transaction 1
+            Transaction::Transfer(Transfer::Withdrawal(
+                Withdrawal::new(Client::new(1), TransactionId::new(3), Decimal::new(100, 0))
+                    .with_timestamp(at(40)),
+            )),
+            // The dispute has already been resolved
+            Transaction::Mutation(Mutation::Resolve(
+                Resolve::new(Client::new(1), TransactionId::new(1)).with_timestamp(at(41)),
+            )),
+        ];
+        let results = vec![
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(crate::error::TransactionError::ResolveError),
+        ];
+        let mut trial_balance = super::TrialBalance::new()
+            .with_dispute_policy(DisputePolicy::new().with_auto_resolve_after(30 * DAY));
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
+            let res = trial_balance.handle_transaction(tx);
+            assert_eq!(res, expected_res, "Failed on index {}", index);
+        }
+        assert_eq!(
+            trial_balance.auto_resolved(),
+            &[Resolve::new(Client::new(1), TransactionId::new(1)).with_timestamp(at(31))]
+        );
+        let mut csv = Vec::new();
+        resolve::to_csv(trial_balance.auto_resolved(), &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            format!(
+                "type,client,tx,amount,timestamp\nresolve,1,1,,{}\n",
+                31 * DAY
+            )
+        );
+    }
+
+    #[test]
+    fn test_retention_by_count() {
+        let deposit = |tx| {
+            Transaction::Transfer(Transfer::Deposit(Deposit::new(
+                Client::new(1),
+                TransactionId::new(tx),
+                Decimal::new(10, 0),
+            )))
+        };
+        let transactions = vec![
+            deposit(1),
+            Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(1),
+                TransactionId::new(1),
+            ))),
+            // An open dispute can not be finalized
+            Transaction::Mutation(Mutation::Finalize(Finalize::new(
+                Client::new(1),
+                TransactionId::new(1),
+            ))),
+            deposit(2),
+            deposit(3),
+            // Evicts transaction 2, transaction 1 is kept while it is under dispute
+            deposit(4),
+            deposit(2),
+            Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(1),
+                TransactionId::new(2),
+            ))),
+            Transaction::Mutation(Mutation::Resolve(Resolve::new(
+                Client::new(1),
+                TransactionId::new(1),
+            ))),
+            Transaction::Mutation(Mutation::Finalize(Finalize::new(
+                Client::new(1),
+                TransactionId::new(4),
+            ))),
+            Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(1),
+                TransactionId::new(4),
+            ))),
+            deposit(4),
+            // Resolving the dispute evicted transaction 3, transaction 1 had moved to the back of the queue
+            Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(1),
+                TransactionId::new(3),
+            ))),
+            Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(1),
+                TransactionId::new(1),
+            ))),
+        ];
+        let results = vec![
+            Ok(()),
+            Ok(()),
+            Err(crate::error::TransactionError::FinalizeError),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(crate::error::TransactionError::DuplicateTransaction(
+                TransactionId::new(2),
+            )),
+            Err(crate::error::TransactionError::TransactionFinalized(
+                TransactionId::new(2),
+            )),
+            Ok(()),
+            Ok(()),
+            Err(crate::error::TransactionError::TransactionFinalized(
+                TransactionId::new(4),
+            )),
+            Err(crate::error::TransactionError::DuplicateTransaction(
+                TransactionId::new(4),
+            )),
+            Err(crate::error::TransactionError::TransactionFinalized(
+                TransactionId::new(3),
+            )),
+            Ok(()),
+        ];
+        let mut trial_balance = super::TrialBalance::new()
+            .with_retention(RetentionPolicy::new().with_max_records(2))
+            .with_verify_every(1);
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
+            let res = trial_balance.handle_transaction(tx);
+            assert_eq!(res, expected_res, "Failed on index {}", index);
+            assert_eq!(trial_balance.violations(), &[], "Failed on index {}", index);
+        }
+        assert_eq!(trial_balance.evicted().len(), 3);
+        assert_eq!(trial_balance.evicted().ranges(), 1);
+        assert_eq!(trial_balance.ledger.len(), 1);
+    }
+
+    #[test]
+    fn test_retention_by_age() {
+        let at = |day: u64| Some(Timestamp::from_secs(day * DAY));
+        let transactions = vec![
+            Transaction::Transfer(Transfer::Deposit(
+                Deposit::new(Client::new(1), TransactionId::new(1), Decimal::new(100, 0))
+                    .with_timestamp(at(0)),
+            )),
+            Transaction::Transfer(Transfer::Deposit(
+                Deposit::new(Client::new(2), TransactionId::new(2), Decimal::new(100, 0))
+                    .with_timestamp(at(10)),
+            )),
+            Transaction::Mutation(Mutation::Dispute(
+                Dispute::new(Client::new(2), TransactionId::new(2)).with_timestamp(at(11)),
+            )),
+            Transaction::Mutation(Mutation::ChargeBack(
+                ChargeBack::new(Client::new(2), TransactionId::new(2)).with_timestamp(at(12)),
+            )),
+            // Evicts transaction 1, transaction 2 is charged back and kept
+            Transaction::Transfer(Transfer::Deposit(
+                Deposit::new(Client::new(1), TransactionId::new(3), Decimal::new(100, 0))
+                    .with_timestamp(at(45)),
+            )),
+            Transaction::Mutation(Mutation::Dispute(
+                Dispute::new(Client::new(1), TransactionId::new(1)).with_timestamp(at(46)),
+            )),
+            Transaction::Mutation(Mutation::Dispute(
+                Dispute::new(Client::new(2), TransactionId::new(2)).with_timestamp(at(46)),
+            )),
+        ];
+        let results = vec![
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(crate::error::TransactionError::TransactionFinalized(
+                TransactionId::new(1),
+            )),
+            Err(crate::error::TransactionError::DisputeError),
+        ];
+        let mut trial_balance = super::TrialBalance::new()
+            .with_retention(RetentionPolicy::new().with_max_age(30 * DAY));
+        for (index, (tx, expected_res)) in transactions.into_iter().zip(results).enumerate() {
             let res = trial_balance.handle_transaction(tx);
             assert_eq!(res, expected_res, "Failed on index {}", index);
         }
+        assert_eq!(trial_balance.evicted().len(), 1);
+        assert_eq!(trial_balance.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_screened() {
+        let rules = RuleSet::from_toml(
+            r#"
+            [[rules]]
+            name = "large-withdrawal"
+            when = "withdrawal_over_average_deposit"
+            factor = 2
+            action = "reject"
+
+            [[rules]]
+            name = "drained"
+            when = "deposit_then_full_withdrawal"
+            action = "hold"
+            "#,
+        )
+        .unwrap();
+        let data = "type, client, tx, amount
+            deposit, 1, 1, 10
+            withdrawal, 1, 2, 10
+            withdrawal, 1, 3, 25
+            withdrawal, 1, 4, 5
+            dispute, 1, 2,
+            deposit, 1, 1, 100
+            withdrawal, 1, 5, 100";
+        let expected = [
+            Ok(()),
+            Err(crate::error::TransactionError::HeldByRule(
+                "drained".to_string(),
+            )),
+            Err(crate::error::TransactionError::RejectedByRule(
+                "large-withdrawal".to_string(),
+            )),
+            Ok(()),
+            // A held transaction is not in the ledger
+            Err(crate::error::TransactionError::MissingTransaction(
+                TransactionId::new(2),
+            )),
+            Err(crate::error::TransactionError::DuplicateTransaction(
+                TransactionId::new(1),
+            )),
+            // The duplicate deposit does not count, so this is not a deposit drained in full
+            Err(crate::error::TransactionError::RejectedByRule(
+                "large-withdrawal".to_string(),
+            )),
+        ];
+        let mut trial_balance = super::TrialBalance::new().with_screener(Screener::new(rules));
+        for (tx, expected) in crate::transaction::transactions(data.as_bytes())
+            .map(Result::unwrap)
+            .zip(expected)
+        {
+            assert_eq!(trial_balance.handle_transaction(tx), expected);
+        }
+        assert_eq!(trial_balance.snapshot()[0].available(), Decimal::new(5, 0));
+        assert_eq!(trial_balance.screener().unwrap().report().len(), 3);
+    }
+
+    #[test]
+    fn test_pending() {
+        let rules = RuleSet::from_toml(
+            r#"
+            [[rules]]
+            name = "large-deposit"
+            when = "deposit_over"
+            amount = 1
+            action = "hold"
+            "#,
+        )
+        .unwrap();
+        let data = "type, client, tx, amount
+            deposit, 1, 1, 10
+            withdrawal, 1, 2, 1
+            release, 1, 1,
+            withdrawal, 1, 3, 1
+            release, 1, 1,
+            deposit, 1, 4, 5
+            dispute, 1, 4,
+            reject, 1, 4,
+            deposit, 1, 4, 5
+            release, 1, 4,";
+        let mut trial_balance = super::TrialBalance::new()
+            .with_screener(Screener::new(rules))
+            .with_journal();
+        let results: Vec<_> = crate::transaction::transactions(data.as_bytes())
+            .map(|tx| trial_balance.handle_transaction(tx.unwrap()))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                // Held as pending, so the funds can not be withdrawn yet
+                Ok(()),
+                Err(crate::error::TransactionError::InsufficientFunds),
+                Ok(()),
+                Ok(()),
+                Err(crate::error::TransactionError::ReleaseError),
+                Ok(()),
+                Err(crate::error::TransactionError::TransactionPending(
+                    TransactionId::new(4)
+                )),
+                Ok(()),
+                Err(crate::error::TransactionError::DuplicateTransaction(
+                    TransactionId::new(4)
+                )),
+                Err(crate::error::TransactionError::TransactionFinalized(
+                    TransactionId::new(4)
+                )),
+            ]
+        );
+        assert_eq!(trial_balance.verify(), Ok(()));
+        assert_eq!(
+            trial_balance
+                .journal()
+                .unwrap()
+                .reconcile(&trial_balance.snapshot()),
+            []
+        );
+        assert_eq!(trial_balance.snapshot()[0].pending(), Decimal::ZERO);
+        assert_eq!(trial_balance.snapshot()[0].available(), Decimal::new(9, 0));
+    }
+
+    #[test]
+    fn test_verify() {
+        let deposit = |client, tx, amount: Decimal| {
+            Transaction::Transfer(Transfer::Deposit(Deposit::new(
+                Client::new(client),
+                TransactionId::new(tx),
+                amount,
+            )))
+        };
+
+        let mut trial_balance = super::TrialBalance::new().with_verify_every(1);
+        trial_balance
+            .handle_transaction(deposit(1, 1, Decimal::new(100, 0)))
+            .unwrap();
+        trial_balance
+            .handle_transaction(Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(1),
+                TransactionId::new(1),
+            ))))
+            .unwrap();
+        assert_eq!(trial_balance.verify(), Ok(()));
+
+        // Rounding the held and available funds separately does not add up to the rounded total
+        trial_balance
+            .handle_transaction(deposit(3, 3, Decimal::new(5, 5)))
+            .unwrap();
+        trial_balance
+            .handle_transaction(deposit(3, 4, Decimal::new(5, 5)))
+            .unwrap();
+        trial_balance
+            .handle_transaction(Transaction::Mutation(Mutation::Dispute(Dispute::new(
+                Client::new(3),
+                TransactionId::new(3),
+            ))))
+            .unwrap();
+
+        // Client 2 charges back the transaction of client 1
+        trial_balance
+            .handle_transaction(deposit(2, 2, Decimal::new(10, 0)))
+            .unwrap();
+        trial_balance
+            .handle_transaction(Transaction::Mutation(Mutation::ChargeBack(
+                ChargeBack::new(Client::new(2), TransactionId::new(1)),
+            )))
+            .unwrap();
+
+        let expected = vec![
+            Violation::HeldMismatch {
+                client: Client::new(1),
+                held: Decimal::new(100, 0),
+                disputed: Decimal::ZERO,
+                transactions: vec![],
+            },
+            Violation::HeldMismatch {
+                client: Client::new(2),
+                held: Decimal::new(-100, 0),
+                disputed: Decimal::ZERO,
+                transactions: vec![],
+            },
+            Violation::LockedWithoutChargeBack {
+                client: Client::new(2),
+            },
+            Violation::TotalMismatch {
+                client: Client::new(3),
+                available: Decimal::ZERO,
+                held: Decimal::ZERO,
+                total: Decimal::new(1, 4),
+            },
+            Violation::ForeignMutation {
+                transaction: TransactionId::new(1),
+                owner: Client::new(1),
+                client: Client::new(2),
+            },
+        ];
+        assert_eq!(trial_balance.verify(), Err(expected.clone()));
+        assert_eq!(trial_balance.violations(), expected);
+
+        // A dispute on a locked account leaves the record undisputed
+        let data = "type, client, tx, amount
+            deposit, 1, 1, 10
+            deposit, 1, 2, 5
+            dispute, 1, 1,
+            chargeback, 1, 1,
+            dispute, 1, 2,";
+        let mut trial_balance = super::TrialBalance::new().with_verify_every(1);
+        let results: Vec<_> = transactions(data.as_bytes())
+            .map(|tx| trial_balance.handle_transaction(tx.unwrap()))
+            .collect();
+        assert_eq!(
+            results.last(),
+            Some(&Err(crate::error::TransactionError::AccountLocked))
+        );
+        assert_eq!(trial_balance.verify(), Ok(()));
+        assert_eq!(trial_balance.violations(), []);
+    }
+
+    #[test]
+    fn test_stats() {
+        let data = "type, client, tx, amount
+            deposit, 1, 1, 10
+            deposit, 1, 2, 5
+            deposit, 1, 2, 5
+            withdrawal, 1, 3, 20
+            withdrawal, 1, 4, 2.5
+            dispute, 1, 1,
+            dispute, 1, 9,
+            chargeback, 1, 1,
+            deposit, 2, 5, 1
+            withdrawal, 1, 6, 1";
+        let mut trial_balance = super::TrialBalance::new().with_stats();
+        for tx in transactions(data.as_bytes()) {
+            let _ = trial_balance.handle_transaction(tx.unwrap());
+        }
+        trial_balance.record_invalid();
+
+        let stats = trial_balance.stats().unwrap();
+        assert_eq!(stats.rows(), 11);
+        assert_eq!(stats.invalid, 1);
+        let deposits = &stats.types["deposit"];
+        assert_eq!(
+            (deposits.read, deposits.applied, deposits.rejected),
+            (4, 3, 1)
+        );
+        assert_eq!(deposits.errors["duplicate_transaction"], 1);
+        let withdrawals = &stats.types["withdrawal"];
+        assert_eq!(withdrawals.errors["insufficient_funds"], 1);
+        assert_eq!(withdrawals.errors["account_locked"], 1);
+        assert_eq!(stats.types["dispute"].errors["missing_transaction"], 1);
+        assert_eq!(stats.deposited, Decimal::new(16, 0));
+        assert_eq!(stats.withdrawn, Decimal::new(25, 1));
+        assert_eq!(stats.held, Decimal::new(10, 0));
+        assert_eq!(stats.charged_back, Decimal::new(10, 0));
+        assert_eq!(stats.locked_accounts, 1);
+
+        let mut merged = stats.clone();
+        merged.merge(&stats);
+        assert_eq!(merged.rows(), 22);
+        assert_eq!(merged.types["withdrawal"].errors["account_locked"], 2);
+        assert!(super::TrialBalance::new().stats().is_none());
+        // Enabling statistics again keeps what was collected
+        let stats = trial_balance.with_stats().stats().unwrap();
+        assert_eq!((stats.rows(), stats.invalid), (11, 1));
     }
 }
@@ -0,0 +1,103 @@
+use std::fmt;
+
+use rust_decimal::Decimal;
+
+use crate::{client::Client, transaction::TransactionId};
+
+/// Represents a broken invariant of the accounts and ledger found by [`TrialBalance::verify`].
+///
+/// [`TrialBalance::verify`]: crate::trial_balance::TrialBalance::verify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The held funds differ from the sum of the disputed transactions of the client.
+    HeldMismatch {
+        client: Client,
+        held: Decimal,
+        disputed: Decimal,
+        transactions: Vec<TransactionId>,
+    },
+    /// The pending funds differ from the sum of the deposits of the client held for review.
+    PendingMismatch {
+        client: Client,
+        pending: Decimal,
+        held_for_review: Decimal,
+        transactions: Vec<TransactionId>,
+    },
+    /// The written total differs from the written available and held funds.
+    TotalMismatch {
+        client: Client,
+        available: Decimal,
+        held: Decimal,
+        total: Decimal,
+    },
+    /// The account is locked while none of its transactions has been charged back.
+    LockedWithoutChargeBack { client: Client },
+    /// The transaction of `owner` has been mutated by another client.
+    ForeignMutation {
+        transaction: TransactionId,
+        owner: Client,
+        client: Client,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::HeldMismatch {
+                client,
+                held,
+                disputed,
+                transactions,
+            } => write!(
+                f,
+                "Client {}: held {} does not equal the disputed amount {} of transactions {:?}",
+                client.id(),
+                held,
+                disputed,
+                transactions
+            ),
+            Violation::PendingMismatch {
+                client,
+                pending,
+                held_for_review,
+                transactions,
+            } => write!(
+                f,
+                "Client {}: pending {} does not equal the amount {} of pending transactions {:?}",
+                client.id(),
+                pending,
+                held_for_review,
+                transactions
+            ),
+            Violation::TotalMismatch {
+                client,
+                available,
+                held,
+                total,
+            } => write!(
+                f,
+                "Client {}: total {} does not equal available {} plus held {}",
+                client.id(),
+                total,
+                available,
+                held
+            ),
+            Violation::LockedWithoutChargeBack { client } => write!(
+                f,
+                "Client {}: account is locked without any charged back transaction",
+                client.id()
+            ),
+            Violation::ForeignMutation {
+                transaction,
+                owner,
+                client,
+            } => write!(
+                f,
+                "Transaction {:?} of client {} was mutated by client {}",
+                transaction,
+                owner.id(),
+                client.id()
+            ),
+        }
+    }
+}